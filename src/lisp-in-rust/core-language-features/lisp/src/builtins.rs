@@ -0,0 +1,255 @@
+use crate::{
+    convert::{FromLisp, ToLisp},
+    eval::{call, expr_to_index, exprs_to_strings, spread_args},
+    expr::{Atom, Builtin, Expr},
+    number::Number,
+    parser::number,
+};
+use anyhow::{anyhow, bail, Result};
+
+// Builtins
+// Every builtin bound in a fresh global environment. Fixed arities use typed
+// arguments, optional and variadic ones take the evaluated arguments as is
+pub(crate) fn builtins() -> Vec<Builtin> {
+    vec![
+        Builtin::new("+", |args| arithmetic(&args, 0, |a, b| Ok(a + b))),
+        Builtin::new("*", |args| arithmetic(&args, 1, |a, b| Ok(a * b))),
+        Builtin::new("-", |args| match args.as_slice() {
+            [] => bail!("`-` expects at least 1 argument"),
+            // Negating directly keeps the sign of `(- 0.0)`, which `0 - x` loses
+            [number] => (-Number::from_lisp(number.clone())?).to_lisp(),
+            args => arithmetic(args, 0, |a, b| Ok(a - b)),
+        }),
+        Builtin::new("/", |args| match args.as_slice() {
+            [] => bail!("`/` expects at least 1 argument"),
+            args => arithmetic(args, 1, Number::checked_div),
+        }),
+        Builtin::new("=", |args| compare(&args, |a, b| a == b)),
+        Builtin::new("<", |args| compare(&args, |a, b| a < b)),
+        Builtin::new(">", |args| compare(&args, |a, b| a > b)),
+        Builtin::new("<=", |args| compare(&args, |a, b| a <= b)),
+        Builtin::new(">=", |args| compare(&args, |a, b| a >= b)),
+        Builtin::new("apply", |args| {
+            let (function, args) = spread_args(args)?;
+            call(function, args)
+        }),
+        Builtin::from_fn("equal?", |a: Expr, b: Expr| equal(&a, &b)),
+        Builtin::new("list", |args| Ok(Expr::list(args))),
+        Builtin::from_fn("cons", Expr::cons),
+        Builtin::from_fn("car", car),
+        Builtin::from_fn("cdr", cdr),
+        Builtin::from_fn("length", |list: Vec<Expr>| list.len()),
+        Builtin::from_fn("reverse", |mut list: Vec<Expr>| {
+            list.reverse();
+            list
+        }),
+        Builtin::new("append", append),
+        Builtin::from_fn("nth", nth),
+        Builtin::from_fn("member", member),
+        Builtin::from_fn("assoc", assoc),
+        Builtin::from_fn("pair?", |expr: Expr| matches!(expr, Expr::Pair(_))),
+        Builtin::from_fn("null?", |expr: Expr| matches!(expr, Expr::Nil)),
+        Builtin::from_fn("string-length", |string: String| string.chars().count()),
+        Builtin::new("substring", substring),
+        Builtin::new("string-append", |args| {
+            Ok(Expr::Constant(Atom::String(
+                exprs_to_strings(&args)?.concat(),
+            )))
+        }),
+        Builtin::from_fn("string->symbol", |string: String| {
+            Expr::Constant(Atom::Symbol(string))
+        }),
+        Builtin::from_fn("symbol->string", symbol_to_string),
+        Builtin::from_fn("number->string", |number: Number| number.to_string()),
+        Builtin::from_fn("string->number", string_to_number),
+        Builtin::new("string-split", string_split),
+        Builtin::new("string-join", string_join),
+        Builtin::from_fn("display", display),
+        Builtin::from_fn("newline", || println!()),
+    ]
+}
+
+fn exprs_to_numbers(exprs: &[Expr]) -> Result<Vec<Number>> {
+    exprs.iter().cloned().map(Number::from_lisp).collect()
+}
+
+// `(/ x)` starts from the identity too, so it inverts
+fn arithmetic(
+    args: &[Expr],
+    identity: i64,
+    operation: fn(Number, Number) -> Result<Number>,
+) -> Result<Expr> {
+    let mut numbers = exprs_to_numbers(args)?;
+    let first = match numbers.len() {
+        0 | 1 => Number::Integer(identity.into()),
+        _ => numbers.remove(0),
+    };
+    numbers.into_iter().try_fold(first, operation)?.to_lisp()
+}
+
+fn compare(args: &[Expr], ordered: fn(&Number, &Number) -> bool) -> Result<Expr> {
+    let numbers = exprs_to_numbers(args)?;
+    numbers
+        .windows(2)
+        .all(|pair| ordered(&pair[0], &pair[1]))
+        .to_lisp()
+}
+
+fn car(list: Expr) -> Result<Expr> {
+    match list {
+        Expr::Pair(pair) => Ok(pair.0.clone()),
+        Expr::Nil => Ok(Expr::Nil),
+        list => bail!("`car` expected list, got {list}"),
+    }
+}
+
+fn cdr(list: Expr) -> Result<Expr> {
+    match list {
+        Expr::Pair(pair) => Ok(pair.1.clone()),
+        Expr::Nil => Ok(Expr::Nil),
+        list => bail!("`cdr` expected list, got {list}"),
+    }
+}
+
+// Structural equality, functions are only equal to themselves. Cars are
+// compared recursively and cdrs in a loop, so long lists don't overflow the
+// stack
+fn equal(mut a: &Expr, mut b: &Expr) -> bool {
+    while let (Expr::Pair(x), Expr::Pair(y)) = (a, b) {
+        if !equal(&x.0, &y.0) {
+            return false;
+        }
+        (a, b) = (&x.1, &y.1);
+    }
+    match (a, b) {
+        (Expr::Constant(a), Expr::Constant(b)) => match (a, b) {
+            (Atom::Boolean(a), Atom::Boolean(b)) => a == b,
+            // Like `eqv?` in Scheme, 1 and 1.0 are `=` but not `equal?`
+            (Atom::Number(a), Atom::Number(b)) => a.is_exact() == b.is_exact() && a == b,
+            (Atom::String(a), Atom::String(b)) => a == b,
+            (Atom::Char(a), Atom::Char(b)) => a == b,
+            (Atom::Symbol(a), Atom::Symbol(b)) => a == b,
+            (Atom::Keyword(a), Atom::Keyword(b)) => a == b,
+            _ => false,
+        },
+        (Expr::Nil, Expr::Nil) => true,
+        (Expr::Builtin(a), Expr::Builtin(b)) => a.name() == b.name(),
+        _ => false,
+    }
+}
+
+// Every list but the last is copied, the last one is shared like in Scheme
+fn append(mut lists: Vec<Expr>) -> Result<Expr> {
+    let last = lists.pop().unwrap_or(Expr::Nil);
+    let mut items = Vec::new();
+    for list in lists {
+        items.extend(list.list_items()?);
+    }
+    Ok(Expr::dotted(items, last))
+}
+
+fn nth(index: usize, list: Vec<Expr>) -> Result<Expr> {
+    let length = list.len();
+    list.into_iter()
+        .nth(index)
+        .ok_or_else(|| anyhow!("`nth` index {index} is out of bounds for length {length}"))
+}
+
+// The first sublist starting with `item`, or #f
+fn member(item: Expr, list: Expr) -> Result<Expr> {
+    let mut list = list;
+    while let Expr::Pair(pair) = &list {
+        if equal(&pair.0, &item) {
+            return Ok(list);
+        }
+        list = pair.1.clone();
+    }
+    match list {
+        Expr::Nil => Ok(Expr::Constant(Atom::Boolean(false))),
+        list => bail!("`member` expected list, got {list}"),
+    }
+}
+
+// The first pair in a list of pairs whose car is `key`, or #f
+fn assoc(key: Expr, list: Vec<Expr>) -> Result<Expr> {
+    for entry in list {
+        match &entry {
+            Expr::Pair(pair) if equal(&pair.0, &key) => return Ok(entry),
+            Expr::Pair(_) => {}
+            entry => bail!("`assoc` expected pair, got {entry}"),
+        }
+    }
+    Ok(Expr::Constant(Atom::Boolean(false)))
+}
+
+fn substring(args: Vec<Expr>) -> Result<Expr> {
+    let (string, start, end) = match args.as_slice() {
+        [Expr::Constant(Atom::String(string)), start] => (string, start, None),
+        [Expr::Constant(Atom::String(string)), start, end] => (string, start, Some(end)),
+        _ => bail!("`substring` expects string, start and optional end"),
+    };
+    let length = string.chars().count();
+    let start = expr_to_index(start)?;
+    let end = match end {
+        Some(end) => expr_to_index(end)?,
+        None => length,
+    };
+    if start > end || end > length {
+        bail!("`substring` range {start}..{end} is out of bounds for length {length}");
+    }
+    let substring = string.chars().skip(start).take(end - start).collect();
+    Ok(Expr::Constant(Atom::String(substring)))
+}
+
+fn symbol_to_string(symbol: Expr) -> Result<String> {
+    match symbol {
+        Expr::Constant(Atom::Symbol(name)) => Ok(name),
+        expr => bail!("Expected symbol, got {expr}"),
+    }
+}
+
+// Like Scheme, text that isn't a number gives #f rather than an error
+fn string_to_number(string: String) -> Expr {
+    match number(&string) {
+        Ok(("", atom)) => Expr::Constant(atom),
+        _ => Expr::Constant(Atom::Boolean(false)),
+    }
+}
+
+fn string_split(args: Vec<Expr>) -> Result<Expr> {
+    let [Expr::Constant(Atom::String(string)), separator @ ..] = args.as_slice() else {
+        bail!("`string-split` expects string and optional separator");
+    };
+    let parts: Vec<&str> = match exprs_to_strings(separator)?.as_slice() {
+        [] => string.split_whitespace().collect(),
+        [separator] => string.split(separator.as_str()).collect(),
+        _ => bail!("`string-split` expects at most one separator"),
+    };
+    let parts = parts
+        .into_iter()
+        .map(|part| Expr::Constant(Atom::String(part.to_string())))
+        .collect();
+    Ok(Expr::list(parts))
+}
+
+fn string_join(args: Vec<Expr>) -> Result<Expr> {
+    let [list, separator @ ..] = args.as_slice() else {
+        bail!("`string-join` expects list and optional separator");
+    };
+    let separator = match exprs_to_strings(separator)?.as_slice() {
+        [] => " ".to_string(),
+        [separator] => separator.clone(),
+        _ => bail!("`string-join` expects at most one separator"),
+    };
+    let strings = exprs_to_strings(&list.list_items()?)?;
+    Ok(Expr::Constant(Atom::String(strings.join(&separator))))
+}
+
+// Strings and characters are written without quotes
+fn display(expr: Expr) {
+    match expr {
+        Expr::Constant(Atom::String(string)) => print!("{string}"),
+        Expr::Constant(Atom::Char(char)) => print!("{char}"),
+        expr => print!("{expr}"),
+    }
+}
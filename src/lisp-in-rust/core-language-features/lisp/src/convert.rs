@@ -0,0 +1,216 @@
+use crate::{
+    expr::{Atom, Builtin, Expr},
+    number::Number,
+};
+use anyhow::{anyhow, bail, Result};
+use num_traits::ToPrimitive;
+
+// Conversions
+/// Types that can be taken out of a Lisp value, used for the arguments of
+/// functions registered with [`Builtin::from_fn`].
+pub trait FromLisp: Sized {
+    fn from_lisp(expr: Expr) -> Result<Self>;
+}
+
+/// Types that can be turned into a Lisp value, used for the return values of
+/// functions registered with [`Builtin::from_fn`].
+pub trait ToLisp {
+    fn to_lisp(self) -> Result<Expr>;
+}
+
+impl FromLisp for Expr {
+    fn from_lisp(expr: Expr) -> Result<Self> {
+        Ok(expr)
+    }
+}
+
+impl ToLisp for Expr {
+    fn to_lisp(self) -> Result<Expr> {
+        Ok(self)
+    }
+}
+
+impl FromLisp for bool {
+    fn from_lisp(expr: Expr) -> Result<Self> {
+        match expr {
+            Expr::Constant(Atom::Boolean(boolean)) => Ok(boolean),
+            expr => bail!("Expected boolean, got {expr}"),
+        }
+    }
+}
+
+impl ToLisp for bool {
+    fn to_lisp(self) -> Result<Expr> {
+        Ok(Expr::Constant(Atom::Boolean(self)))
+    }
+}
+
+impl FromLisp for Number {
+    fn from_lisp(expr: Expr) -> Result<Self> {
+        match expr {
+            Expr::Constant(Atom::Number(number)) => Ok(number),
+            expr => bail!("Expected number, got {expr}"),
+        }
+    }
+}
+
+impl ToLisp for Number {
+    fn to_lisp(self) -> Result<Expr> {
+        Ok(Expr::Constant(Atom::Number(self)))
+    }
+}
+
+// Integers have to fit the Rust type, rationals and floats are never truncated
+macro_rules! integer_conversions {
+    ($($type:ty => $method:ident),*) => {
+        $(
+            impl FromLisp for $type {
+                fn from_lisp(expr: Expr) -> Result<Self> {
+                    match expr {
+                        Expr::Constant(Atom::Number(Number::Integer(integer))) => integer
+                            .$method()
+                            .ok_or_else(|| anyhow!("{integer} doesn't fit in {}", stringify!($type))),
+                        expr => bail!("Expected integer, got {expr}"),
+                    }
+                }
+            }
+
+            impl ToLisp for $type {
+                fn to_lisp(self) -> Result<Expr> {
+                    Ok(Expr::Constant(Atom::Number(Number::Integer(self.into()))))
+                }
+            }
+        )*
+    };
+}
+
+integer_conversions!(
+    i32 => to_i32,
+    i64 => to_i64,
+    isize => to_isize,
+    u32 => to_u32,
+    u64 => to_u64,
+    usize => to_usize
+);
+
+impl FromLisp for f64 {
+    fn from_lisp(expr: Expr) -> Result<Self> {
+        Ok(Number::from_lisp(expr)?.to_float())
+    }
+}
+
+impl ToLisp for f64 {
+    fn to_lisp(self) -> Result<Expr> {
+        Ok(Expr::Constant(Atom::Number(Number::Float(self))))
+    }
+}
+
+impl FromLisp for String {
+    fn from_lisp(expr: Expr) -> Result<Self> {
+        match expr {
+            Expr::Constant(Atom::String(string)) => Ok(string),
+            expr => bail!("Expected string, got {expr}"),
+        }
+    }
+}
+
+impl ToLisp for String {
+    fn to_lisp(self) -> Result<Expr> {
+        Ok(Expr::Constant(Atom::String(self)))
+    }
+}
+
+impl ToLisp for &str {
+    fn to_lisp(self) -> Result<Expr> {
+        self.to_string().to_lisp()
+    }
+}
+
+impl FromLisp for char {
+    fn from_lisp(expr: Expr) -> Result<Self> {
+        match expr {
+            Expr::Constant(Atom::Char(char)) => Ok(char),
+            expr => bail!("Expected character, got {expr}"),
+        }
+    }
+}
+
+impl ToLisp for char {
+    fn to_lisp(self) -> Result<Expr> {
+        Ok(Expr::Constant(Atom::Char(self)))
+    }
+}
+
+impl<T: FromLisp> FromLisp for Vec<T> {
+    fn from_lisp(expr: Expr) -> Result<Self> {
+        expr.list_items()?.into_iter().map(T::from_lisp).collect()
+    }
+}
+
+impl<T: ToLisp> ToLisp for Vec<T> {
+    fn to_lisp(self) -> Result<Expr> {
+        let items = self
+            .into_iter()
+            .map(T::to_lisp)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Expr::list(items))
+    }
+}
+
+impl ToLisp for () {
+    fn to_lisp(self) -> Result<Expr> {
+        Ok(Expr::Nil)
+    }
+}
+
+// Errors returned by the Rust function become Lisp errors
+impl<T: ToLisp> ToLisp for Result<T> {
+    fn to_lisp(self) -> Result<Expr> {
+        self?.to_lisp()
+    }
+}
+
+/// Rust functions whose arguments and return value convert to and from Lisp
+/// values. `Args` is the tuple of argument types, so closures of any arity up
+/// to six can be registered.
+pub trait IntoBuiltin<Args> {
+    fn into_builtin(self, name: &str) -> Builtin;
+}
+
+macro_rules! into_builtin {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> IntoBuiltin<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: ToLisp,
+            $($arg: FromLisp,)*
+        {
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn into_builtin(self, name: &str) -> Builtin {
+                let owned = name.to_string();
+                Builtin::new(name, move |args| {
+                    let arity = <[&str]>::len(&[$(stringify!($arg)),*]);
+                    if args.len() != arity {
+                        let plural = if arity == 1 { "" } else { "s" };
+                        bail!("`{owned}` expects {arity} argument{plural}, got {}", args.len());
+                    }
+                    let mut args = args.into_iter().enumerate();
+                    $(
+                        let (index, expr) = args.next().unwrap_or((0, Expr::Nil));
+                        let $arg = <$arg>::from_lisp(expr)
+                            .map_err(|error| anyhow!("`{owned}` argument {}: {error}", index + 1))?;
+                    )*
+                    self($($arg),*).to_lisp()
+                })
+            }
+        }
+    };
+}
+
+into_builtin!();
+into_builtin!(A);
+into_builtin!(A, B);
+into_builtin!(A, B, C);
+into_builtin!(A, B, C, D);
+into_builtin!(A, B, C, D, E);
+into_builtin!(A, B, C, D, E, G);
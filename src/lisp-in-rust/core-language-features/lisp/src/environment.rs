@@ -0,0 +1,158 @@
+use crate::{expr::Expr, modules::Modules};
+use anyhow::{bail, Result};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+// Environment
+#[derive(Default)]
+struct Scope {
+    bindings: HashMap<String, Expr>,
+    parent: Option<Environment>,
+    // Shared by every scope of an interpreter
+    modules: Rc<RefCell<Modules>>,
+    // Set from another thread, like a Ctrl-C handler, to stop evaluation
+    interrupt: Arc<AtomicBool>,
+    stack: Rc<Stack>,
+}
+
+// Evaluating nested calls recurses on the Rust stack, so how much of it has
+// been used is measured from where the outermost `eval` started, and deep
+// recursion fails before the stack overflows
+struct Stack {
+    base: Cell<Option<usize>>,
+    limit: Cell<usize>,
+}
+
+impl Default for Stack {
+    fn default() -> Self {
+        // Half of the 2 MiB a spawned thread gets
+        Stack {
+            base: Cell::new(None),
+            limit: Cell::new(1 << 20),
+        }
+    }
+}
+
+// Forgets the base of the stack when the outermost `eval` returns
+pub(crate) struct StackGuard(Option<Rc<Stack>>);
+
+impl Drop for StackGuard {
+    fn drop(&mut self) {
+        if let Some(stack) = &self.0 {
+            stack.base.set(None);
+        }
+    }
+}
+
+/// The scope a closure or macro captured, only the interpreter can look
+/// inside it.
+#[derive(Clone, Default)]
+pub struct Environment(Rc<RefCell<Scope>>);
+
+impl Environment {
+    pub(crate) fn extend(&self) -> Self {
+        let scope = Scope {
+            bindings: HashMap::new(),
+            parent: Some(self.clone()),
+            modules: self.modules(),
+            interrupt: self.interrupt(),
+            stack: self.0.borrow().stack.clone(),
+        };
+        Environment(Rc::new(RefCell::new(scope)))
+    }
+
+    pub(crate) fn global(&self) -> Environment {
+        let parent = self.0.borrow().parent.clone();
+        match parent {
+            Some(parent) => parent.global(),
+            None => self.clone(),
+        }
+    }
+
+    pub(crate) fn modules(&self) -> Rc<RefCell<Modules>> {
+        self.0.borrow().modules.clone()
+    }
+
+    pub(crate) fn interrupt(&self) -> Arc<AtomicBool> {
+        self.0.borrow().interrupt.clone()
+    }
+
+    // Clears the interrupt flag, returning whether it was set
+    pub(crate) fn take_interrupt(&self) -> bool {
+        self.0.borrow().interrupt.swap(false, Ordering::Relaxed)
+    }
+
+    // Fails once evaluation has used more of the stack than the limit
+    pub(crate) fn enter(&self) -> Result<StackGuard> {
+        let stack = &self.0.borrow().stack;
+        let marker = 0u8;
+        let here = std::ptr::addr_of!(marker) as usize;
+        match stack.base.get() {
+            None => {
+                stack.base.set(Some(here));
+                Ok(StackGuard(Some(stack.clone())))
+            }
+            Some(base) if base.abs_diff(here) > stack.limit.get() => {
+                bail!("Recursion too deep")
+            }
+            Some(_) => Ok(StackGuard(None)),
+        }
+    }
+
+    pub(crate) fn set_stack_limit(&self, bytes: usize) {
+        self.0.borrow().stack.limit.set(bytes);
+    }
+
+    // Names bound in this scope, not its parents
+    pub(crate) fn names(&self) -> Vec<String> {
+        self.0.borrow().bindings.keys().cloned().collect()
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<Expr> {
+        let scope = self.0.borrow();
+        match scope.bindings.get(name) {
+            Some(value) => Some(value.clone()),
+            None => scope.parent.as_ref().and_then(|parent| parent.get(name)),
+        }
+    }
+
+    pub(crate) fn define(&self, name: String, value: Expr) {
+        self.0.borrow_mut().bindings.insert(name, value);
+    }
+
+    // Forgets the bindings of this scope and every loaded module
+    pub(crate) fn clear(&self) {
+        let modules = std::mem::take(&mut *self.modules().borrow_mut());
+        modules.unload();
+        self.0.borrow_mut().bindings.clear();
+    }
+
+    // Changes the binding in the nearest scope that has `name`
+    pub(crate) fn set(&self, name: &str, value: Expr) -> Result<()> {
+        let mut scope = self.0.borrow_mut();
+        if let Some(binding) = scope.bindings.get_mut(name) {
+            *binding = value;
+            return Ok(());
+        }
+        match &scope.parent {
+            Some(parent) => parent.set(name, value),
+            None => bail!("`{name}` is not defined"),
+        }
+    }
+}
+
+// A closure usually lives inside the environment it captured, so printing the
+// bindings here would recurse forever
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Environment")
+    }
+}
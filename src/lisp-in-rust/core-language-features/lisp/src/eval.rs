@@ -0,0 +1,616 @@
+use crate::{
+    environment::Environment,
+    expr::{Atom, Expr},
+    modules::{define_module, load_file, provide, require, resolve},
+    number::Number,
+    params::Params,
+};
+use anyhow::{anyhow, bail, Result};
+use num_traits::ToPrimitive;
+use std::rc::Rc;
+
+// Helpers
+pub(crate) fn exprs_to_strings(exprs: &[Expr]) -> Result<Vec<String>> {
+    let strings = exprs
+        .iter()
+        .map(|expr| match expr {
+            Expr::Constant(Atom::String(string)) => Ok(string.clone()),
+            Expr::Constant(Atom::Char(char)) => Ok(char.to_string()),
+            atom => Err(anyhow!("Expected string, got {atom}")),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(strings)
+}
+
+pub(crate) fn expr_to_index(expr: &Expr) -> Result<usize> {
+    match expr {
+        Expr::Constant(Atom::Number(Number::Integer(integer))) => integer
+            .to_usize()
+            .ok_or_else(|| anyhow!("Expected index, got {integer}")),
+        atom => bail!("Expected index, got {atom}"),
+    }
+}
+
+// The `((name value) ...)` of `let` forms
+fn bindings(expr: &Expr) -> Result<Vec<(&String, &Expr)>> {
+    let items: Vec<&Expr> = match expr {
+        Expr::Call(head, tail) => std::iter::once(&**head).chain(tail).collect(),
+        Expr::Nil => Vec::new(),
+        expr => bail!("Expected bindings, got {expr}"),
+    };
+    items
+        .into_iter()
+        .map(|binding| match binding {
+            Expr::Call(head, tail) => match (&**head, tail.as_slice()) {
+                (Expr::Constant(Atom::Symbol(name)), [value]) => Ok((name, value)),
+                _ => bail!("Expected binding, got {binding}"),
+            },
+            binding => bail!("Expected binding, got {binding}"),
+        })
+        .collect()
+}
+
+// What a missing `if` branch or an empty body evaluates
+const NIL: &Expr = &Expr::Nil;
+
+// Everything except #f counts as true
+fn is_truthy(expr: &Expr) -> bool {
+    !matches!(expr, Expr::Constant(Atom::Boolean(false)))
+}
+
+// Evaluator
+/// Names that `eval` handles itself instead of calling a function.
+pub const SPECIAL_FORMS: &[&str] = &[
+    "begin",
+    "cond",
+    "define",
+    "defmacro",
+    "if",
+    "lambda",
+    "let",
+    "let*",
+    "letrec",
+    "load",
+    "macroexpand",
+    "macroexpand-1",
+    "module",
+    "provide",
+    "quasiquote",
+    "quote",
+    "require",
+    "set!",
+    "unless",
+    "when",
+];
+
+// What's left to do after one step of evaluating a form: its value, or the
+// expression in tail position that `eval` loops around to
+enum Step<'a> {
+    Value(Expr),
+    Tail(&'a Expr, Environment),
+    // A closure body or macro expansion, which `eval` keeps alive while it
+    // evaluates the body and its last expression
+    Body(Rc<[Expr]>, Environment),
+}
+
+pub(crate) fn eval(expr: &Expr, environment: &Environment) -> Result<Expr> {
+    let _stack = environment.enter()?;
+    let mut expr = expr;
+    let mut environment = environment.clone();
+    let mut owner: Rc<[Expr]>;
+    // Expressions in tail position replace `expr` and `environment` and loop
+    // around instead of recursing, so tail calls don't grow the Rust stack.
+    // Each form is evaluated by its own function, which keeps this frame
+    // small for the calls that do recurse
+    loop {
+        if environment.take_interrupt() {
+            bail!("Interrupted");
+        }
+        let step = match expr {
+            Expr::Constant(Atom::Symbol(name)) => Step::Value(
+                environment
+                    .get(name)
+                    .ok_or_else(|| anyhow!("`{name}` is not defined"))?,
+            ),
+            Expr::Define(Atom::Symbol(name), value) => {
+                let value = eval(value, &environment)?;
+                environment.define(name.clone(), value);
+                Step::Value(Expr::Nil)
+            }
+            Expr::Lambda(params, body) => Step::Value(Expr::Closure(
+                params.clone(),
+                body.clone(),
+                environment.clone(),
+            )),
+            Expr::Call(head, tail) => eval_call(head, tail, &environment)?,
+            Expr::Closure(_, _, _)
+            | Expr::Macro(_, _, _)
+            | Expr::Builtin(_)
+            | Expr::Constant(_)
+            | Expr::Nil => Step::Value(expr.clone()),
+            _ => bail!("Invalid expression: {expr}"),
+        };
+        match step {
+            Step::Value(value) => return Ok(value),
+            Step::Tail(tail, scope) => {
+                expr = tail;
+                environment = scope;
+            }
+            Step::Body(body, scope) => {
+                owner = body;
+                expr = eval_body(&owner, &scope)?;
+                environment = scope;
+            }
+        }
+    }
+}
+
+fn eval_call<'a>(head: &Expr, tail: &'a [Expr], environment: &Environment) -> Result<Step<'a>> {
+    let Expr::Constant(Atom::Symbol(name)) = head else {
+        return call_function(head, tail, environment);
+    };
+    match name.as_str() {
+        "begin" => eval_body(tail, environment).map(|last| Step::Tail(last, environment.clone())),
+        "cond" => eval_cond(tail, environment),
+        "if" => eval_if(tail, environment),
+        "let" => eval_let(tail, environment),
+        "let*" => eval_let_star(tail, environment),
+        "letrec" => eval_letrec(tail, environment),
+        "when" | "unless" => eval_when(name, tail, environment),
+        "define" => eval_define(tail).map(Step::Value),
+        "defmacro" => eval_defmacro(tail, environment).map(Step::Value),
+        "lambda" => eval_lambda(tail).map(Step::Value),
+        "load" => eval_load(tail, environment).map(Step::Value),
+        "macroexpand" | "macroexpand-1" => {
+            eval_macroexpand(name, tail, environment).map(Step::Value)
+        }
+        "module" => eval_module(tail, environment).map(Step::Value),
+        "provide" => eval_provide(tail, environment).map(Step::Value),
+        "quasiquote" => eval_quasiquote(tail, environment).map(Step::Value),
+        "quote" => eval_quote(tail).map(Step::Value),
+        "require" => eval_require(tail, environment).map(Step::Value),
+        "set!" => eval_set(tail, environment).map(Step::Value),
+        _ => call_function(head, tail, environment),
+    }
+}
+
+fn call_function<'a>(head: &Expr, tail: &'a [Expr], environment: &Environment) -> Result<Step<'a>> {
+    let head = eval(head, environment)?;
+    if let Expr::Macro(params, body, closure) = head {
+        let tail = tail.iter().cloned().map(quote).collect();
+        let expansion = code(apply_macro(&params, &body, &closure, tail)?);
+        return Ok(Step::Body(Rc::new([expansion]), environment.clone()));
+    }
+    let mut function = head;
+    // A loop rather than an iterator chain, whose adapters would add frames
+    // to every nested call in debug builds
+    let mut args = Vec::with_capacity(tail.len());
+    for expr in tail {
+        args.push(eval(expr, environment)?);
+    }
+    // Calls through `apply` are made here instead of by the builtin, so
+    // they're tail calls too
+    while is_apply(&function) {
+        (function, args) = spread_args(args)?;
+    }
+    match function {
+        Expr::Closure(params, body, closure) => {
+            let scope = call_scope(&params, &body, &closure, args)?;
+            Ok(Step::Body(body, scope))
+        }
+        Expr::Builtin(builtin) => Ok(Step::Value(builtin.call(args)?)),
+        function => bail!("Invalid function: {function}"),
+    }
+}
+
+// Special forms
+// The parser only reads lambdas with valid parameters, so this reports what's
+// wrong with the others
+fn eval_lambda(tail: &[Expr]) -> Result<Expr> {
+    let Some(params) = tail.first() else {
+        bail!("`lambda` expects parameters and body");
+    };
+    Params::from_data(&quote(params.clone()))?;
+    bail!("`lambda` expects a body");
+}
+
+// Likewise the parser only reads `(define name value)`
+fn eval_define(tail: &[Expr]) -> Result<Expr> {
+    match tail {
+        [name @ Expr::Call(head, args), ..] => {
+            let args = Expr::list(args.iter().cloned().map(quote).collect());
+            bail!("`define` expected name, got {name}. Functions are defined with (define {head} (lambda {args} ...))")
+        }
+        [name, _] => bail!("`define` expected name, got {name}"),
+        _ => bail!("`define` expects 2 arguments, got {}", tail.len()),
+    }
+}
+
+fn eval_set(tail: &[Expr], environment: &Environment) -> Result<Expr> {
+    let [name, value] = tail else {
+        bail!("`set!` expects 2 arguments, got {}", tail.len());
+    };
+    let Expr::Constant(Atom::Symbol(name)) = name else {
+        bail!("`set!` expected name, got {name}");
+    };
+    let value = eval(value, environment)?;
+    environment.set(name, value)?;
+    Ok(Expr::Nil)
+}
+
+fn eval_quote(tail: &[Expr]) -> Result<Expr> {
+    match tail {
+        [expr] => Ok(quote(expr.clone())),
+        _ => bail!("`quote` expects 1 argument, got {}", tail.len()),
+    }
+}
+
+fn eval_quasiquote(tail: &[Expr], environment: &Environment) -> Result<Expr> {
+    match tail {
+        [expr] => quasiquote(quote(expr.clone()), environment),
+        _ => bail!("`quasiquote` expects 1 argument, got {}", tail.len()),
+    }
+}
+
+fn eval_defmacro(tail: &[Expr], environment: &Environment) -> Result<Expr> {
+    let (name, args, body) = match tail {
+        [name, args, body @ ..] if !body.is_empty() => (name, args, body),
+        _ => bail!(
+            "`defmacro` expects name, arguments and body, got {}",
+            tail.len()
+        ),
+    };
+    let Expr::Constant(Atom::Symbol(name)) = name else {
+        bail!("`defmacro` expected name, got {name}");
+    };
+    let params = Params::from_data(&quote(args.clone()))?;
+    let value = Expr::Macro(Rc::new(params), body.into(), environment.clone());
+    environment.define(name.clone(), value);
+    Ok(Expr::Nil)
+}
+
+fn eval_load(tail: &[Expr], environment: &Environment) -> Result<Expr> {
+    let [path] = tail else {
+        bail!("`load` expects 1 argument, got {}", tail.len());
+    };
+    let Expr::Constant(Atom::String(path)) = eval(path, environment)? else {
+        bail!("`load` expected path");
+    };
+    load_file(&resolve(&path, environment)?, environment)?;
+    Ok(Expr::Nil)
+}
+
+fn eval_require(tail: &[Expr], environment: &Environment) -> Result<Expr> {
+    for spec in tail {
+        require(spec, environment)?;
+    }
+    Ok(Expr::Nil)
+}
+
+fn eval_provide(tail: &[Expr], environment: &Environment) -> Result<Expr> {
+    let names = tail
+        .iter()
+        .map(|expr| match expr {
+            Expr::Constant(Atom::Symbol(name)) => Ok(name.clone()),
+            expr => Err(anyhow!("`provide` expected name, got {expr}")),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    provide(names, environment)?;
+    Ok(Expr::Nil)
+}
+
+fn eval_module(tail: &[Expr], environment: &Environment) -> Result<Expr> {
+    let Some((name, body)) = tail.split_first() else {
+        bail!("`module` expects a name");
+    };
+    let Expr::Constant(Atom::Symbol(name)) = name else {
+        bail!("`module` expected name");
+    };
+    define_module(name.clone(), environment, |namespace| {
+        body.iter()
+            .try_for_each(|expr| eval(expr, namespace).map(drop))
+    })?;
+    Ok(Expr::Nil)
+}
+
+fn eval_macroexpand(name: &str, tail: &[Expr], environment: &Environment) -> Result<Expr> {
+    let [form] = tail else {
+        bail!("`{name}` expects 1 argument, got {}", tail.len());
+    };
+    let form = eval(form, environment)?;
+    match name {
+        "macroexpand-1" => Ok(macroexpand_1(&form, environment)?.unwrap_or(form)),
+        _ => macroexpand(form, environment),
+    }
+}
+
+fn eval_if<'a>(tail: &'a [Expr], environment: &Environment) -> Result<Step<'a>> {
+    let (condition, consequent, alternative) = match tail {
+        [condition, consequent] => (condition, consequent, NIL),
+        [condition, consequent, alternative] => (condition, consequent, alternative),
+        _ => bail!("`if` expects 2 or 3 arguments, got {}", tail.len()),
+    };
+    let branch = match is_truthy(&eval(condition, environment)?) {
+        true => consequent,
+        false => alternative,
+    };
+    Ok(Step::Tail(branch, environment.clone()))
+}
+
+fn eval_cond<'a>(tail: &'a [Expr], environment: &Environment) -> Result<Step<'a>> {
+    for clause in tail {
+        let Expr::Call(test, body) = clause else {
+            bail!("`cond` expected clause, got {clause}");
+        };
+        if test.is_symbol("else") {
+            return Ok(Step::Tail(
+                eval_body(body, environment)?,
+                environment.clone(),
+            ));
+        }
+        let test = eval(test, environment)?;
+        if is_truthy(&test) {
+            // A clause without a body returns the test's value
+            if body.is_empty() {
+                return Ok(Step::Value(test));
+            }
+            return Ok(Step::Tail(
+                eval_body(body, environment)?,
+                environment.clone(),
+            ));
+        }
+    }
+    Ok(Step::Value(Expr::Nil))
+}
+
+fn eval_when<'a>(name: &str, tail: &'a [Expr], environment: &Environment) -> Result<Step<'a>> {
+    let Some((condition, body)) = tail.split_first() else {
+        bail!("`when` and `unless` expect a condition");
+    };
+    let condition = eval(condition, environment)?;
+    if is_truthy(&condition) != (name == "when") {
+        return Ok(Step::Value(Expr::Nil));
+    }
+    Ok(Step::Tail(
+        eval_body(body, environment)?,
+        environment.clone(),
+    ))
+}
+
+fn eval_let<'a>(tail: &'a [Expr], environment: &Environment) -> Result<Step<'a>> {
+    let Some((first, rest)) = tail.split_first() else {
+        bail!("`let` expects bindings");
+    };
+    // Named let binds `name` to a function of the variables with the body,
+    // and calls it with their initial values
+    if let Expr::Constant(Atom::Symbol(name)) = first {
+        let Some((bindings_code, body)) = rest.split_first() else {
+            bail!("`let` expects bindings");
+        };
+        let (names, values): (Vec<_>, Vec<_>) = bindings(bindings_code)?.into_iter().unzip();
+        let values = values
+            .into_iter()
+            .map(|value| eval(value, environment))
+            .collect::<Result<Vec<_>>>()?;
+        let params = Params {
+            required: names.into_iter().cloned().collect(),
+            name: Some(name.clone()),
+            ..Params::default()
+        };
+        let body: Rc<[Expr]> = body.into();
+        let scope = call_scope(&Rc::new(params), &body, environment, values)?;
+        return Ok(Step::Body(body, scope));
+    }
+    let scope = environment.extend();
+    for (name, value) in bindings(first)? {
+        let value = eval(value, environment)?;
+        scope.define(name.clone(), value);
+    }
+    Ok(Step::Tail(eval_body(rest, &scope)?, scope))
+}
+
+// `let*` sees the bindings before it. Each binding gets a scope of its own,
+// so a closure made by one doesn't see later bindings of the same name
+fn eval_let_star<'a>(tail: &'a [Expr], environment: &Environment) -> Result<Step<'a>> {
+    let Some((bindings_code, body)) = tail.split_first() else {
+        bail!("`let*` expects bindings");
+    };
+    let mut scope = environment.extend();
+    for (name, value) in bindings(bindings_code)? {
+        let value = eval(value, &scope)?;
+        scope = scope.extend();
+        scope.define(name.clone(), value);
+    }
+    Ok(Step::Tail(eval_body(body, &scope)?, scope))
+}
+
+// `letrec` sees all of its bindings, so its functions can call each other
+fn eval_letrec<'a>(tail: &'a [Expr], environment: &Environment) -> Result<Step<'a>> {
+    let Some((bindings_code, body)) = tail.split_first() else {
+        bail!("`letrec` expects bindings");
+    };
+    let scope = environment.extend();
+    for (name, value) in bindings(bindings_code)? {
+        let value = eval(value, &scope)?;
+        scope.define(name.clone(), value);
+    }
+    Ok(Step::Tail(eval_body(body, &scope)?, scope))
+}
+
+// Code is parsed into special forms and calls, quoting turns it back into the
+// plain lists it was written as
+pub(crate) fn quote(expr: Expr) -> Expr {
+    match expr {
+        Expr::Define(name, value) => Expr::list(vec![
+            Expr::symbol("define"),
+            Expr::Constant(name),
+            quote(*value),
+        ]),
+        Expr::Lambda(params, body) => {
+            let head = [Expr::symbol("lambda"), params.to_data()];
+            Expr::list(
+                head.into_iter()
+                    .chain(body.iter().cloned().map(quote))
+                    .collect(),
+            )
+        }
+        Expr::Call(head, tail) => {
+            let items = std::iter::once(*head).chain(tail).map(quote).collect();
+            Expr::list(items)
+        }
+        Expr::Pair(pair) => {
+            let (car, cdr) = (*pair).clone();
+            Expr::cons(quote(car), quote(cdr))
+        }
+        expr => expr,
+    }
+}
+
+// The inverse of `quote`, turns lists built at runtime, like the output of a
+// macro, back into code that can be evaluated
+pub(crate) fn code(data: Expr) -> Expr {
+    let items = match data {
+        Expr::Pair(_) => match data.list_items() {
+            Ok(items) => items,
+            // Improper lists are literal pairs, the same as in the parser
+            Err(_) => return data,
+        },
+        data => return data,
+    };
+    match items.as_slice() {
+        [define, Expr::Constant(name @ Atom::Symbol(_)), value] if define.is_symbol("define") => {
+            return Expr::Define(name.clone(), Box::new(code(value.clone())));
+        }
+        [lambda, params, body @ ..] if lambda.is_symbol("lambda") && !body.is_empty() => {
+            if let Ok(params) = Params::from_data(params) {
+                return Expr::Lambda(Rc::new(params), body.iter().cloned().map(code).collect());
+            }
+        }
+        _ => {}
+    }
+    let mut items = items.into_iter().map(code);
+    let head = items.next().unwrap_or(Expr::Nil);
+    Expr::Call(Box::new(head), items.collect())
+}
+
+// Walks a quoted template, evaluating `,x` and splicing in the items of `,@xs`.
+// Nested quasiquotes aren't tracked, so every unquote belongs to the outermost
+fn quasiquote(data: Expr, environment: &Environment) -> Result<Expr> {
+    if let Some(expr) = unquoted(&data, "unquote") {
+        return eval(&code(expr), environment);
+    }
+    let Expr::Pair(pair) = data else {
+        return Ok(data);
+    };
+    let (car, cdr) = (*pair).clone();
+    let rest = quasiquote(cdr, environment)?;
+    match unquoted(&car, "unquote-splicing") {
+        Some(expr) => {
+            let items = eval(&code(expr), environment)?.list_items()?;
+            Ok(Expr::dotted(items, rest))
+        }
+        None => Ok(Expr::cons(quasiquote(car, environment)?, rest)),
+    }
+}
+
+// The `x` in `(name x)`
+fn unquoted(data: &Expr, name: &str) -> Option<Expr> {
+    match data.list_items().ok()?.as_slice() {
+        [head, expr] if head.is_symbol(name) => Some(expr.clone()),
+        _ => None,
+    }
+}
+
+// Macros get their arguments as unevaluated lists and return the code to run
+fn apply_macro(
+    params: &Params,
+    body: &[Expr],
+    closure: &Environment,
+    tail: Vec<Expr>,
+) -> Result<Expr> {
+    let scope = closure.extend();
+    params.bind(tail, &scope)?;
+    let last = eval_body(body, &scope)?;
+    eval(last, &scope)
+}
+
+// Expands `form` until it isn't a call to a macro
+pub(crate) fn macroexpand(mut form: Expr, environment: &Environment) -> Result<Expr> {
+    while let Some(expansion) = macroexpand_1(&form, environment)? {
+        form = expansion;
+    }
+    Ok(form)
+}
+
+// Expands `form` once if it's a call to a macro
+fn macroexpand_1(form: &Expr, environment: &Environment) -> Result<Option<Expr>> {
+    let Expr::Pair(pair) = form else {
+        return Ok(None);
+    };
+    let Expr::Constant(Atom::Symbol(name)) = &pair.0 else {
+        return Ok(None);
+    };
+    let Some(Expr::Macro(params, body, closure)) = environment.get(name) else {
+        return Ok(None);
+    };
+    let expansion = apply_macro(&params, &body, &closure, pair.1.list_items()?)?;
+    Ok(Some(expansion))
+}
+
+fn is_apply(function: &Expr) -> bool {
+    matches!(function, Expr::Builtin(builtin) if builtin.name() == "apply")
+}
+
+// `(apply f a b list)` calls `f` with `a`, `b` and the items of `list`
+pub(crate) fn spread_args(mut args: Vec<Expr>) -> Result<(Expr, Vec<Expr>)> {
+    if args.len() < 2 {
+        bail!("`apply` expects a function and a list, got {}", args.len());
+    }
+    let function = args.remove(0);
+    let list = args.pop().unwrap_or(Expr::Nil);
+    args.extend(list.list_items()?);
+    Ok((function, args))
+}
+
+// Calls a function from Rust, for builtins that take functions
+pub(crate) fn call(function: Expr, args: Vec<Expr>) -> Result<Expr> {
+    match function {
+        Expr::Closure(params, body, closure) => {
+            let scope = call_scope(&params, &body, &closure, args)?;
+            let last = eval_body(&body, &scope)?;
+            eval(last, &scope)
+        }
+        Expr::Builtin(builtin) => builtin.call(args),
+        function => bail!("Invalid function: {function}"),
+    }
+}
+
+// The scope a closure call runs in, with the arguments bound. A named let
+// function binds itself here rather than in the scope it captured, which
+// would then hold the function that holds it and never be freed
+fn call_scope(
+    params: &Rc<Params>,
+    body: &Rc<[Expr]>,
+    closure: &Environment,
+    args: Vec<Expr>,
+) -> Result<Environment> {
+    let scope = closure.extend();
+    if let Some(name) = &params.name {
+        let function = Expr::Closure(params.clone(), body.clone(), closure.clone());
+        scope.define(name.clone(), function);
+    }
+    params.bind(args, &scope)?;
+    Ok(scope)
+}
+
+// Evaluates every expression of a body except the last one, which is returned
+// so the caller can evaluate it in tail position
+fn eval_body<'a>(body: &'a [Expr], environment: &Environment) -> Result<&'a Expr> {
+    let Some((last, init)) = body.split_last() else {
+        return Ok(NIL);
+    };
+    for expr in init {
+        eval(expr, environment)?;
+    }
+    Ok(last)
+}
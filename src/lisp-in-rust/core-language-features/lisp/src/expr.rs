@@ -0,0 +1,143 @@
+use crate::{convert::IntoBuiltin, environment::Environment, number::Number, params::Params};
+use anyhow::{bail, Result};
+use std::{fmt, ops::Deref, rc::Rc};
+
+/// A value that isn't a list or a function. New kinds of atoms may be added,
+/// so matches need a wildcard arm.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Atom {
+    Boolean(bool),
+    Number(Number),
+    String(String),
+    Char(char),
+    Symbol(String),
+    Keyword(String),
+}
+
+/// Parsed code and the values it evaluates to. `Define`, `Call` and `Lambda`
+/// only appear in code, evaluation never returns them. New variants may be
+/// added, so matches need a wildcard arm.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Expr {
+    Constant(Atom),
+    Define(Atom, Box<Expr>),
+    Call(Box<Expr>, Vec<Expr>),
+    Lambda(Rc<Params>, Rc<[Expr]>),
+    Closure(Rc<Params>, Rc<[Expr]>, Environment),
+    Macro(Rc<Params>, Rc<[Expr]>, Environment),
+    Builtin(Builtin),
+    Pair(Pair),
+    Nil,
+}
+
+impl Expr {
+    pub fn is_symbol(&self, name: &str) -> bool {
+        matches!(self, Expr::Constant(Atom::Symbol(symbol)) if symbol == name)
+    }
+
+    pub fn symbol(name: &str) -> Expr {
+        Expr::Constant(Atom::Symbol(name.to_string()))
+    }
+
+    pub fn cons(car: Expr, cdr: Expr) -> Expr {
+        Expr::Pair(Pair {
+            cell: Rc::new((car, cdr)),
+        })
+    }
+
+    pub fn list(items: Vec<Expr>) -> Expr {
+        Expr::dotted(items, Expr::Nil)
+    }
+
+    // A list whose last cdr is `last` instead of nil
+    pub(crate) fn dotted(items: Vec<Expr>, last: Expr) -> Expr {
+        items
+            .into_iter()
+            .rev()
+            .fold(last, |cdr, car| Expr::cons(car, cdr))
+    }
+
+    pub fn list_items(&self) -> Result<Vec<Expr>> {
+        let mut items = Vec::new();
+        let mut list = self;
+        loop {
+            match list {
+                Expr::Pair(pair) => {
+                    items.push(pair.0.clone());
+                    list = &pair.1;
+                }
+                Expr::Nil => return Ok(items),
+                _ => bail!("Expected list, got {self}"),
+            }
+        }
+    }
+}
+
+/// A cons cell, shared between the lists that contain it.
+#[derive(Debug, Clone)]
+pub struct Pair {
+    cell: Rc<(Expr, Expr)>,
+}
+
+impl Deref for Pair {
+    type Target = (Expr, Expr);
+
+    fn deref(&self) -> &(Expr, Expr) {
+        &self.cell
+    }
+}
+
+// Dropping the cdr recursively would overflow the stack on long lists, so
+// cells that aren't shared are unlinked in a loop instead
+impl Drop for Pair {
+    fn drop(&mut self) {
+        let Some(pair) = Rc::get_mut(&mut self.cell) else {
+            return;
+        };
+        let mut rest = std::mem::replace(&mut pair.1, Expr::Nil);
+        while let Expr::Pair(mut next) = rest {
+            let Some(pair) = Rc::get_mut(&mut next.cell) else {
+                break;
+            };
+            rest = std::mem::replace(&mut pair.1, Expr::Nil);
+        }
+    }
+}
+
+// A function implemented in Rust
+#[derive(Clone)]
+pub struct Builtin {
+    name: String,
+    function: Rc<dyn Fn(Vec<Expr>) -> Result<Expr>>,
+}
+
+impl Builtin {
+    pub fn new(name: &str, function: impl Fn(Vec<Expr>) -> Result<Expr> + 'static) -> Self {
+        Builtin {
+            name: name.to_string(),
+            function: Rc::new(function),
+        }
+    }
+
+    /// Wraps a Rust function with typed arguments, checking the arity and
+    /// converting each argument with [`FromLisp`](crate::FromLisp).
+    pub fn from_fn<Args>(name: &str, function: impl IntoBuiltin<Args>) -> Self {
+        function.into_builtin(name)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn call(&self, args: Vec<Expr>) -> Result<Expr> {
+        (self.function)(args)
+    }
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Builtin({})", self.name)
+    }
+}
@@ -0,0 +1,207 @@
+mod builtins;
+mod convert;
+mod environment;
+mod eval;
+mod expr;
+mod modules;
+mod number;
+mod params;
+mod parser;
+mod printer;
+
+pub use convert::{FromLisp, IntoBuiltin, ToLisp};
+pub use eval::SPECIAL_FORMS;
+pub use expr::{Atom, Builtin, Expr, Pair};
+pub use number::Number;
+pub use params::Params;
+pub use parser::{parse, ParseError};
+
+use anyhow::{Context, Result};
+use environment::Environment;
+use std::{
+    fs,
+    ops::Deref,
+    path::Path,
+    rc::Rc,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+/// A Lisp interpreter with its own global environment.
+///
+/// ```
+/// let interpreter = lisp::Interpreter::new();
+/// let output = interpreter.eval_str("(+ 1 2)").unwrap();
+/// assert_eq!(output.to_string(), "3");
+/// ```
+#[derive(Clone)]
+pub struct Interpreter {
+    environment: Rc<Global>,
+    std: bool,
+}
+
+// Functions capture the global environment they're defined in, so it's
+// cleared when the last clone of the interpreter is dropped, or the cycle
+// would keep both alive
+struct Global(Environment);
+
+impl Deref for Global {
+    type Target = Environment;
+
+    fn deref(&self) -> &Environment {
+        &self.0
+    }
+}
+
+impl Drop for Global {
+    fn drop(&mut self) {
+        self.0.clear();
+    }
+}
+
+impl Interpreter {
+    /// Creates an interpreter with the standard library loaded.
+    pub fn new() -> Self {
+        Interpreter::with_std(true)
+    }
+
+    /// Creates an interpreter with only the special forms and builtins.
+    pub fn without_std() -> Self {
+        Interpreter::with_std(false)
+    }
+
+    fn with_std(std: bool) -> Self {
+        let interpreter = Interpreter {
+            environment: Rc::new(Global(Environment::default())),
+            std,
+        };
+        interpreter.reset();
+        interpreter
+    }
+
+    /// Forgets every global definition and loaded module, going back to the
+    /// environment the interpreter was created with. Clones of the
+    /// interpreter share its environment, so they're reset too.
+    ///
+    /// ```
+    /// let interpreter = lisp::Interpreter::new();
+    /// interpreter.eval_str("(define x 1)").unwrap();
+    /// interpreter.reset();
+    /// assert!(interpreter.get_global("x").is_none());
+    /// assert!(interpreter.get_global("map").is_some());
+    /// ```
+    pub fn reset(&self) {
+        self.environment.clear();
+        for builtin in builtins::builtins() {
+            self.environment
+                .define(builtin.name().to_string(), Expr::Builtin(builtin));
+        }
+        if self.std {
+            modules::load(include_str!("std.lisp"), &self.environment)
+                .expect("std.lisp should load");
+        }
+    }
+
+    /// Evaluates a single expression in the global environment.
+    pub fn eval(&self, expr: Expr) -> Result<Expr> {
+        eval::eval(&expr, &self.environment)
+    }
+
+    /// Expands `expr` while it's a call to a macro, like `macroexpand` does
+    /// for a quoted form, and returns the code to evaluate in its place.
+    /// Macro calls nested inside the expansion are left as they are.
+    ///
+    /// ```
+    /// let interpreter = lisp::Interpreter::new();
+    /// interpreter.eval_str("(defmacro twice (x) `(begin ,x ,x))").unwrap();
+    /// let expr = lisp::parse("(twice (display 1))").unwrap().remove(0);
+    /// let expansion = interpreter.macroexpand(&expr).unwrap();
+    /// assert_eq!(expansion.to_string(), "(begin (display 1) (display 1))");
+    /// ```
+    pub fn macroexpand(&self, expr: &Expr) -> Result<Expr> {
+        let expansion = eval::macroexpand(eval::quote(expr.clone()), &self.environment)?;
+        Ok(eval::code(expansion))
+    }
+
+    /// Evaluates every expression in `source`, returning the value of the
+    /// last one.
+    pub fn eval_str(&self, source: &str) -> Result<Expr> {
+        let mut output = Expr::Nil;
+        for expr in parse(source)? {
+            output = self.eval(expr)?;
+        }
+        Ok(output)
+    }
+
+    /// Evaluates a file, with `load` and `require` inside it resolving paths
+    /// relative to it.
+    pub fn load_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let path =
+            fs::canonicalize(path).with_context(|| format!("Failed to find {}", path.display()))?;
+        modules::load_file(&path, &self.environment)
+    }
+
+    /// A flag that makes the running evaluation fail with an `Interrupted`
+    /// error once it's set, for example from a Ctrl-C handler.
+    pub fn interrupt_handle(&self) -> Arc<AtomicBool> {
+        self.environment.interrupt()
+    }
+
+    /// How many bytes of the Rust stack evaluation may use before it fails
+    /// with `Recursion too deep`, instead of overflowing the stack. The
+    /// default of 1 MiB suits the 2 MiB stack of a spawned thread, so raise
+    /// it when evaluating on a bigger one.
+    pub fn set_stack_limit(&self, bytes: usize) {
+        self.environment.set_stack_limit(bytes);
+    }
+
+    /// Binds `name` in the global environment, replacing any earlier value.
+    pub fn define_global(&self, name: &str, value: Expr) {
+        self.environment.global().define(name.to_string(), value);
+    }
+
+    /// The value bound to `name` in the global environment, if any.
+    pub fn get_global(&self, name: &str) -> Option<Expr> {
+        self.environment.global().get(name)
+    }
+
+    /// Every name bound in the global environment, sorted.
+    pub fn global_names(&self) -> Vec<String> {
+        let mut names = self.environment.global().names();
+        names.sort();
+        names
+    }
+
+    /// Binds `name` to a function implemented in Rust, which gets its
+    /// arguments already evaluated. Use this for optional or variadic
+    /// arguments, otherwise [`define_fn`](Interpreter::define_fn) checks
+    /// them for you.
+    pub fn define_builtin(
+        &self,
+        name: &str,
+        function: impl Fn(Vec<Expr>) -> Result<Expr> + 'static,
+    ) {
+        self.define_global(name, Expr::Builtin(Builtin::new(name, function)));
+    }
+
+    /// Binds `name` to a Rust function with typed arguments, which are
+    /// checked and converted before it's called.
+    ///
+    /// ```
+    /// let interpreter = lisp::Interpreter::new();
+    /// interpreter.define_fn("add", |a: isize, b: isize| a + b);
+    /// let output = interpreter.eval_str("(add 1 2)").unwrap();
+    /// assert_eq!(output.to_string(), "3");
+    /// assert!(interpreter.eval_str("(add 1)").is_err());
+    /// assert!(interpreter.eval_str("(add 1 \"2\")").is_err());
+    /// ```
+    pub fn define_fn<Args>(&self, name: &str, function: impl IntoBuiltin<Args>) {
+        self.define_global(name, Expr::Builtin(Builtin::from_fn(name, function)));
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
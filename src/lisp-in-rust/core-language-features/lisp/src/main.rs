@@ -1,240 +1,595 @@
 use anyhow::{anyhow, bail, Result};
-use nom::{
-    branch::alt,
-    bytes::complete::tag,
-    character::complete::{alpha1, digit1, multispace0, multispace1},
-    combinator::map,
-    multi::{many0, many1},
-    sequence::{delimited, pair, preceded, separated_pair, terminated},
-    IResult,
+use lisp::{parse, Atom, Expr, Interpreter, SPECIAL_FORMS};
+use rustyline::{
+    completion::Completer,
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::{Hint, Hinter},
+    validate::{ValidationContext, ValidationResult, Validator},
+    Context, Editor,
+};
+use rustyline_derive::Helper;
+use std::{
+    borrow::Cow,
+    fs,
+    io::IsTerminal,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+    time::Instant,
 };
-use rustyline::{validate::MatchingBracketValidator, Editor};
-use rustyline_derive::{Completer, Helper, Highlighter, Hinter, Validator};
-use std::collections::HashMap;
 
-// Parser
-#[derive(Debug, Clone)]
-enum Operator {
-    Plus,
-    Minus,
-    Divide,
-    Multiply,
+// Rustyline
+#[derive(Helper)]
+struct Helper {
+    // Shares the REPL's environment, so new definitions show up right away
+    interpreter: Interpreter,
 }
 
-#[derive(Debug, Clone)]
-enum Atom {
-    Number(isize),
-    Operator(Operator),
-    Symbol(String),
+// The symbol that ends at `pos`, as its start and text
+fn word_before(line: &str, pos: usize) -> (usize, &str) {
+    let start = line[..pos]
+        .rfind(|char: char| char.is_whitespace() || "()'`,\"".contains(char))
+        .map_or(0, |index| index + 1);
+    (start, &line[start..pos])
 }
 
-// Atom
-fn operator(input: &str) -> IResult<&str, Atom> {
-    let plus = map(tag("+"), |_| Operator::Plus);
-    let minus = map(tag("-"), |_| Operator::Minus);
-    let divide = map(tag("/"), |_| Operator::Divide);
-    let multiply = map(tag("*"), |_| Operator::Multiply);
-    map(alt((plus, minus, divide, multiply)), Atom::Operator)(input)
-}
+// Completes special forms and everything bound in the global environment
+impl Completer for Helper {
+    type Candidate = String;
 
-fn number(input: &str) -> IResult<&str, Atom> {
-    map(digit1, |digits: &str| {
-        Atom::Number(digits.parse::<isize>().unwrap())
-    })(input)
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let (start, prefix) = word_before(line, pos);
+        if prefix.is_empty() {
+            return Ok((start, Vec::new()));
+        }
+        let names = SPECIAL_FORMS.iter().map(|name| name.to_string());
+        let mut candidates: Vec<String> = names
+            .chain(self.interpreter.global_names())
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+        Ok((start, candidates))
+    }
 }
 
-fn symbol(input: &str) -> IResult<&str, Atom> {
-    map(alpha1, |name: &str| Atom::Symbol(name.to_string()))(input)
+#[derive(Clone, Copy, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    String,
+    Number,
+    // Booleans, characters, keywords and nil
+    Constant,
+    SpecialForm,
+    Bound,
+    Unbound,
+    // Whitespace, quote marks and anything that doesn't read
+    Plain,
 }
 
-fn atom(input: &str) -> IResult<&str, Atom> {
-    let options = alt((symbol, operator, number));
-    delimited(multispace0, options, multispace0)(input)
+impl Token {
+    // ANSI color codes
+    fn color(self) -> Option<&'static str> {
+        match self {
+            Token::String => Some("32"),
+            Token::Number | Token::Constant => Some("33"),
+            Token::SpecialForm => Some("1;35"),
+            Token::Bound => Some("36"),
+            Token::Unbound | Token::Open | Token::Close | Token::Plain => None,
+        }
+    }
 }
 
-// Expr
-#[derive(Debug, Clone)]
-enum Expr {
-    Constant(Atom),
-    Define(Atom, Box<Expr>),
-    Call(Box<Expr>, Vec<Expr>),
-    Lambda(Vec<Atom>, Box<Expr>),
-    List(Vec<Expr>),
-    Nil,
+const MATCHING_BRACKET: &str = "1;34";
+
+impl Helper {
+    // Splits a line that may still be incomplete, so it can't use `parse`
+    // for anything but single words
+    fn tokens(&self, line: &str) -> Vec<(Range<usize>, Token)> {
+        let is_delimiter = |char: char| char.is_whitespace() || "()'`,\"".contains(char);
+        let mut tokens = Vec::new();
+        let mut chars = line.char_indices().peekable();
+        while let Some((start, char)) = chars.next() {
+            let token = match char {
+                '(' => Token::Open,
+                ')' => Token::Close,
+                '"' => {
+                    while let Some((_, char)) = chars.next() {
+                        match char {
+                            '\\' => {
+                                chars.next();
+                            }
+                            '"' => break,
+                            _ => {}
+                        }
+                    }
+                    Token::String
+                }
+                char if is_delimiter(char) => Token::Plain,
+                _ => {
+                    // The character after `#\` belongs to it, even `#\(`
+                    if line[start..].starts_with("#\\") {
+                        chars.next();
+                        chars.next();
+                    }
+                    while chars.next_if(|(_, char)| !is_delimiter(*char)).is_some() {}
+                    let end = chars.peek().map_or(line.len(), |(index, _)| *index);
+                    self.word_token(&line[start..end])
+                }
+            };
+            let end = chars.peek().map_or(line.len(), |(index, _)| *index);
+            tokens.push((start..end, token));
+        }
+        tokens
+    }
+
+    // Only the global environment is known while typing, so parameters and
+    // let bindings look unbound too, which is why unbound names aren't
+    // colored as errors
+    fn word_token(&self, word: &str) -> Token {
+        if SPECIAL_FORMS.contains(&word) {
+            return Token::SpecialForm;
+        }
+        match parse(word).as_deref() {
+            Ok([Expr::Constant(Atom::Number(_))]) => Token::Number,
+            Ok([Expr::Constant(Atom::Symbol(name))]) => match self.interpreter.get_global(name) {
+                Some(_) => Token::Bound,
+                None => Token::Unbound,
+            },
+            Ok([Expr::Constant(_) | Expr::Nil]) => Token::Constant,
+            _ => Token::Plain,
+        }
+    }
 }
 
-impl Expr {
-    fn is_symbol(&self, name: &str) -> bool {
-        match self {
-            Expr::Constant(Atom::Symbol(symbol)) if symbol == name => true,
-            _ => false,
+// Pairs up the brackets of a line, by token index. Unclosed ones are left
+// on the stack that's returned
+fn brackets(tokens: &[(Range<usize>, Token)]) -> (Vec<(usize, usize)>, Vec<usize>) {
+    let mut pairs = Vec::new();
+    let mut open = Vec::new();
+    for (index, (_, token)) in tokens.iter().enumerate() {
+        match token {
+            Token::Open => open.push(index),
+            Token::Close => {
+                if let Some(start) = open.pop() {
+                    pairs.push((start, index));
+                }
+            }
+            _ => {}
         }
     }
+    (pairs, open)
 }
 
-fn constant(input: &str) -> IResult<&str, Expr> {
-    map(atom, Expr::Constant)(input)
+// The token index of the bracket matching the one under or just before the
+// cursor
+fn matching_bracket(tokens: &[(Range<usize>, Token)], pos: usize) -> Option<usize> {
+    let (pairs, _) = brackets(tokens);
+    let bracket = tokens
+        .iter()
+        .position(|(range, _)| range.start == pos)
+        .filter(|index| matches!(tokens[*index].1, Token::Open | Token::Close))
+        .or_else(|| {
+            let index = tokens.iter().position(|(range, _)| range.end == pos)?;
+            matches!(tokens[index].1, Token::Open | Token::Close).then_some(index)
+        })?;
+    pairs.iter().find_map(|&(open, close)| {
+        if bracket == open {
+            Some(close)
+        } else if bracket == close {
+            Some(open)
+        } else {
+            None
+        }
+    })
 }
 
-fn call(input: &str) -> IResult<&str, Expr> {
-    let form = pair(expr, many0(expr));
-    let call = map(form, |(head, tail)| Expr::Call(Box::new(head), tail));
-    delimited(tag("("), call, tag(")"))(input)
+impl Highlighter for Helper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        let tokens = self.tokens(line);
+        let matching = matching_bracket(&tokens, pos);
+        let mut highlighted = String::with_capacity(line.len() * 2);
+        for (index, (range, token)) in tokens.iter().enumerate() {
+            let color = match matching {
+                Some(matching) if matching == index => Some(MATCHING_BRACKET),
+                _ => token.color(),
+            };
+            match color {
+                Some(color) => {
+                    highlighted += &format!("\x1b[{color}m{}\x1b[0m", &line[range.clone()])
+                }
+                None => highlighted += &line[range.clone()],
+            }
+        }
+        Cow::Owned(highlighted)
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("\x1b[2m{hint}\x1b[0m"))
+    }
+
+    // Moving the cursor changes the matching bracket, so always redraw
+    fn highlight_char(&self, _: &str, _: usize) -> bool {
+        true
+    }
 }
 
-fn define(input: &str) -> IResult<&str, Expr> {
-    let form = preceded(tag("define"), pair(atom, expr));
-    let define = map(form, |(name, value)| Expr::Define(name, Box::new(value)));
-    delimited(tag("("), define, tag(")"))(input)
+// The rest of a function's parameter list. It's only shown, since the right
+// arrow would otherwise insert the parameter names into the line
+struct ParamsHint(String);
+
+impl Hint for ParamsHint {
+    fn display(&self) -> &str {
+        &self.0
+    }
+
+    fn completion(&self) -> Option<&str> {
+        None
+    }
 }
 
-fn lambda(input: &str) -> IResult<&str, Expr> {
-    let args = delimited(tag("("), many0(atom), tag(")"));
-    let form = preceded(
-        terminated(tag("lambda"), multispace1),
-        separated_pair(args, multispace1, expr),
-    );
-    let lambda = map(form, |(args, body)| Expr::Lambda(args, Box::new(body)));
-    delimited(tag("("), lambda, tag(")"))(input)
+// After `(name ` shows the parameters of the lambda or macro bound to name
+impl Hinter for Helper {
+    type Hint = ParamsHint;
+
+    fn hint(&self, line: &str, pos: usize, _: &Context<'_>) -> Option<ParamsHint> {
+        if pos < line.len() {
+            return None;
+        }
+        let tokens = self.tokens(line);
+        let (_, open) = brackets(&tokens);
+        let start = tokens[*open.last()?].0.end;
+        let inside = &line[start..];
+        let name = inside.trim_end();
+        if name.is_empty() || name.len() == inside.len() || name.contains(char::is_whitespace) {
+            return None;
+        }
+        let params = match self.interpreter.get_global(name)? {
+            Expr::Closure(params, _, _) | Expr::Macro(params, _, _) => params.to_string(),
+            _ => return None,
+        };
+        // `(a b . rest)` continues as `a b . rest)`, a lone `args` as `. args)`
+        let hint = match params.strip_prefix('(') {
+            Some(params) => params.to_string(),
+            None => format!(". {params})"),
+        };
+        Some(ParamsHint(hint))
+    }
 }
 
-fn expr(input: &str) -> IResult<&str, Expr> {
-    let expr = alt((define, lambda, call, constant));
-    delimited(multispace0, expr, multispace0)(input)
+// Keep reading lines while a list or string is still open
+impl Validator for Helper {
+    fn validate(&self, context: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        match parse(context.input()) {
+            Err(error) if error.is_incomplete() => Ok(ValidationResult::Incomplete),
+            _ => Ok(ValidationResult::Valid(None)),
+        }
+    }
 }
 
-// Final parser
-fn parse(input: &str) -> IResult<&str, Vec<Expr>> {
-    many1(expr)(input)
+// Command line
+const USAGE: &str = "\
+Usage: lisp [OPTIONS] [FILE [ARGS...]]
+
+Runs FILE, or `-` for stdin, with ARGS bound to *argv*. Without FILE or -e
+the REPL is started, unless a script is piped through stdin.
+
+Before the REPL starts, $LISP_INIT or ~/.lisprc is loaded if it exists.
+History is kept in $XDG_DATA_HOME/lisp/history, which defaults to
+~/.local/share/lisp/history. Type ,help in the REPL for its commands.
+
+Options:
+  -e EXPR     Evaluate EXPR and print the results
+  -i          Start the REPL after running FILE or -e
+  --no-std    Don't load the standard library
+  --no-init   Don't load the init file
+  -h, --help  Print this help";
+
+#[derive(Default)]
+struct Options {
+    expressions: Vec<String>,
+    file: Option<String>,
+    args: Vec<String>,
+    interactive: bool,
+    no_std: bool,
+    no_init: bool,
 }
 
-// Helpers
-fn exprs_to_numbers(exprs: &[Expr]) -> Result<Vec<isize>> {
-    let numbers = exprs
-        .iter()
-        .map(|expr| match expr {
-            Expr::Constant(Atom::Number(number)) => Ok(*number),
-            atom => Err(anyhow!("Expected number, got {atom:?}")),
-        })
-        .collect::<Result<Vec<_>, _>>()?;
-    Ok(numbers)
-}
-
-// Evaluator
-fn eval(expr: Expr, environment: &mut HashMap<String, Expr>) -> Result<Expr> {
-    let output = match expr {
-        Expr::Constant(Atom::Symbol(name)) => environment
-            .get(&name)
-            .ok_or_else(|| anyhow!("`{name}` is not defined"))?
-            .clone(),
-        Expr::Define(Atom::Symbol(name), value) => {
-            let value = eval(*value, environment)?;
-            environment.insert(name, value);
-            Expr::Nil
-        }
-        Expr::Call(head, tail) => {
-            if head.is_symbol("quote") {
-                tail.get(0)
-                    .ok_or_else(|| anyhow!("`quote` expects 1 argument, got {}", tail.len()))?
-                    .clone()
-            } else {
-                let head = eval(*head, environment)?;
-                let tail = tail
-                    .into_iter()
-                    .map(|expr| eval(expr, environment))
-                    .collect::<Result<Vec<_>, _>>()?;
-                match head {
-                    Expr::Constant(Atom::Operator(operator)) => {
-                        let numbers = exprs_to_numbers(&tail)?;
-                        let total = numbers
-                            .into_iter()
-                            .reduce(|total, number| match operator {
-                                Operator::Plus => total + number,
-                                Operator::Minus => total - number,
-                                Operator::Divide => total / number,
-                                Operator::Multiply => total * number,
-                            })
-                            .ok_or_else(|| anyhow!("Tail is empty"))?;
-                        Expr::Constant(Atom::Number(total))
-                    }
-                    Expr::Lambda(args, body) => {
-                        let scope = environment.clone();
-                        for (arg, expr) in args.into_iter().zip(tail.into_iter()) {
-                            match arg {
-                                Atom::Symbol(name) => {
-                                    environment.insert(name, expr);
-                                }
-                                _ => bail!("Invalid symbol: {arg:?}"),
-                            }
-                        }
-                        let output = eval(*body, environment)?;
-                        *environment = scope;
-                        output
-                    }
-                    head if head.is_symbol("list") => Expr::List(tail),
-                    head if head.is_symbol("car") => match tail.as_slice() {
-                        [Expr::List(items)] => items.get(0).map(Clone::clone).unwrap_or(Expr::Nil),
-                        _ => bail!("`car` expected list, got {tail:?}"),
-                    },
-                    head if head.is_symbol("cdr") => match tail.as_slice() {
-                        [Expr::List(items)] if items.len() > 1 => Expr::List(items[1..].to_vec()),
-                        [Expr::List(_)] => Expr::Nil,
-                        _ => bail!("`cdr` expected list, got {tail:?}"),
-                    },
-                    _ => bail!("Invalid function: {head:?}"),
-                }
+fn options(mut args: impl Iterator<Item = String>) -> Result<Options> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-e" => {
+                let expression = args.next().ok_or_else(|| anyhow!("`-e` expects EXPR"))?;
+                options.expressions.push(expression);
+            }
+            "-i" => options.interactive = true,
+            "--no-std" => options.no_std = true,
+            "--no-init" => options.no_init = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            "--" => {
+                options.file = args.next();
+                break;
+            }
+            flag if flag.starts_with('-') && flag != "-" => bail!("Unknown option `{flag}`"),
+            _ => {
+                options.file = Some(arg);
+                break;
             }
         }
-        Expr::Lambda(_, _) | Expr::Constant(_) | Expr::Nil => expr,
-        _ => bail!(anyhow!("Invalid expression: {expr:?}")),
+    }
+    // Everything after FILE belongs to the script, even if it looks like a flag
+    options.args = args.collect();
+    Ok(options)
+}
+
+const STACK_SIZE: usize = 256 << 20;
+
+fn run(options: Options) -> Result<()> {
+    let interpreter = if options.no_std {
+        Interpreter::without_std()
+    } else {
+        Interpreter::new()
+    };
+    // Leaves plenty of room for the frames below the evaluator
+    interpreter.set_stack_limit(STACK_SIZE / 2);
+    let argv = options
+        .args
+        .into_iter()
+        .map(|arg| Expr::Constant(Atom::String(arg)))
+        .collect();
+    interpreter.define_global("*argv*", Expr::list(argv));
+
+    // Without FILE or -e a script can still be piped through stdin
+    let piped =
+        options.file.is_none() && options.expressions.is_empty() && !std::io::stdin().is_terminal();
+    let ran = piped || options.file.is_some() || !options.expressions.is_empty();
+    let interactive = options.interactive || !ran;
+    // Scripts shouldn't depend on the user's setup, so only the REPL loads it
+    if interactive && !options.no_init {
+        if let Some(init) = init_file() {
+            if let Err(error) = interpreter.load_file(&init) {
+                eprintln!("{error:#}");
+            }
+        }
+    }
+
+    for expression in &options.expressions {
+        for expr in parse(expression)? {
+            println!("{}", interpreter.eval(expr)?);
+        }
+    }
+    match options.file.as_deref() {
+        Some("-") => {
+            interpreter.eval_str(&std::io::read_to_string(std::io::stdin())?)?;
+        }
+        Some(file) => interpreter.load_file(file)?,
+        None if piped => {
+            interpreter.eval_str(&std::io::read_to_string(std::io::stdin())?)?;
+        }
+        None => {}
+    }
+    if interactive {
+        repl(&interpreter)?;
+    }
+    Ok(())
+}
+
+// Files
+fn home() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(PathBuf::from)
+}
+
+fn history_file() -> Option<PathBuf> {
+    let data = match std::env::var_os("XDG_DATA_HOME") {
+        Some(data) if !data.is_empty() => PathBuf::from(data),
+        _ => home()?.join(".local/share"),
     };
-    Ok(output)
+    Some(data.join("lisp").join("history"))
+}
+
+// $LISP_INIT has to exist once it's set, ~/.lisprc is optional
+fn init_file() -> Option<PathBuf> {
+    if let Some(init) = std::env::var_os("LISP_INIT") {
+        return Some(PathBuf::from(init));
+    }
+    let init = home()?.join(".lisprc");
+    init.exists().then_some(init)
 }
 
-fn load(body: &str, environment: &mut HashMap<String, Expr>) -> Result<()> {
-    match parse(body) {
-        Ok((_, exprs)) => for expr in exprs {
-            eval(expr, environment)?;
+// Adds the entries since the last call to the file, so history survives a
+// crash and REPLs running side by side don't overwrite each other's
+fn append_history(editor: &mut Editor<Helper>, history: &Path) -> Result<()> {
+    if let Some(parent) = history.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    editor.append_history(history)?;
+    Ok(())
+}
+
+// Meta-commands
+const COMMANDS: &str = "\
+,help          Show this help
+,env [PREFIX]  List the global bindings, or the ones starting with PREFIX
+,doc NAME      Show how NAME is defined
+,time EXPR     Evaluate EXPR and print how long it took
+,load FILE     Evaluate FILE
+,reset         Forget everything defined since the standard library loaded
+,debug         Toggle printing each expression with its macros expanded";
+
+// Handles a REPL line starting with a comma, which isn't valid outside
+// quasiquote anyway
+fn command(interpreter: &Interpreter, input: &str, debug: &mut bool) -> Result<()> {
+    let (name, arg) = match input.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (input, ""),
+    };
+    match (name, arg) {
+        ("help", _) => println!("{COMMANDS}"),
+        ("env", prefix) => {
+            let names = interpreter.global_names();
+            for name in names.iter().filter(|name| name.starts_with(prefix)) {
+                if let Some(value) = interpreter.get_global(name) {
+                    println!("{name} = {value}");
+                }
+            }
+        }
+        ("doc" | "time" | "load", "") => bail!("`,{name}` expects an argument, try `,help`"),
+        ("doc", name) => doc(interpreter, name)?,
+        ("time", source) => {
+            let start = Instant::now();
+            let output = interpreter.eval_str(source)?;
+            let elapsed = start.elapsed();
+            println!("{output}");
+            println!("Took {elapsed:?}");
         }
-        Err(_) => bail!("Failed to load body"),
+        ("load", file) => interpreter.load_file(file)?,
+        ("reset", _) => {
+            // The script arguments were bound at startup, so they stay
+            let argv = interpreter.get_global("*argv*");
+            interpreter.reset();
+            if let Some(argv) = argv {
+                interpreter.define_global("*argv*", argv);
+            }
+        }
+        ("debug", _) => {
+            *debug = !*debug;
+            println!("Debug printing is {}", if *debug { "on" } else { "off" });
+        }
+        _ => bail!("Unknown command `,{name}`, try `,help`"),
     }
     Ok(())
 }
 
-// Rustyline
-#[derive(Helper, Completer, Hinter, Validator, Highlighter)]
-struct Helper {
-    #[rustyline(Validator)]
-    validator: MatchingBracketValidator,
+fn doc(interpreter: &Interpreter, name: &str) -> Result<()> {
+    if SPECIAL_FORMS.contains(&name) {
+        println!("`{name}` is a special form");
+        return Ok(());
+    }
+    let format_body = |body: &[Expr]| {
+        let body: Vec<String> = body.iter().map(ToString::to_string).collect();
+        body.join(" ")
+    };
+    match interpreter.get_global(name) {
+        Some(Expr::Closure(params, body, _)) => {
+            println!("(lambda {params} {})", format_body(&body));
+        }
+        Some(Expr::Macro(params, body, _)) => {
+            println!("(defmacro {name} {params} {})", format_body(&body));
+        }
+        Some(Expr::Builtin(_)) => println!("`{name}` is a builtin function"),
+        Some(value) => println!("{value}"),
+        None => bail!("`{name}` is not defined"),
+    }
+    Ok(())
 }
 
-fn main() -> Result<()> {
+fn repl(interpreter: &Interpreter) -> Result<()> {
     // Create rustyline editor
     let mut editor = Editor::new()?;
-    let helper = Helper {
-        validator: MatchingBracketValidator::new(),
-    };
-    editor.set_helper(Some(helper));
+    editor.set_helper(Some(Helper {
+        interpreter: interpreter.clone(),
+    }));
+    let mut history = history_file();
+    if let Some(history) = &history {
+        // There's no history yet on the first run
+        editor.load_history(history).ok();
+    }
+
+    // While reading a line the terminal is in raw mode and rustyline sees
+    // Ctrl-C itself, so the signal only arrives during evaluation
+    let interrupt = interpreter.interrupt_handle();
+    let handler = interrupt.clone();
+    ctrlc::set_handler(move || handler.store(true, Ordering::Relaxed))?;
 
-    // Read our custom std library
-    let mut environment = HashMap::new();
-    let std = include_str!("std.lisp");
-    load(std, &mut environment)?;
-    
     // Read lines and eval them
+    let mut debug = false;
     loop {
-        let input = editor.readline(">> ")?;
+        let input = match editor.readline(">> ") {
+            Ok(input) => input,
+            // Ctrl-C cancels the line and Ctrl-D quits
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(error) => return Err(error.into()),
+        };
         editor.add_history_entry(&input);
+        if let Some(path) = &history {
+            if let Err(error) = append_history(&mut editor, path) {
+                // Warns once instead of on every line
+                eprintln!("Failed to save history to {}: {error}", path.display());
+                history = None;
+            }
+        }
+        interrupt.store(false, Ordering::Relaxed);
+        if let Some(input) = input.trim().strip_prefix(',') {
+            if let Err(error) = command(interpreter, input, &mut debug) {
+                println!("{error:#}");
+            }
+            continue;
+        }
         match parse(&input) {
-            Ok((_, exprs)) => {
-                for expr in exprs {
-                    match eval(expr, &mut environment) {
-                        Ok(output) => println!("{output:?}"),
-                        Err(error) => println!("{error}"),
+            Ok(exprs) => {
+                for mut expr in exprs {
+                    // Prints the code an expression runs, and runs that so
+                    // its macros aren't expanded twice
+                    if debug {
+                        match interpreter.macroexpand(&expr) {
+                            Ok(expansion) => {
+                                println!("{expansion}");
+                                expr = expansion;
+                            }
+                            Err(error) => {
+                                println!("{error:#}");
+                                break;
+                            }
+                        }
+                    }
+                    match interpreter.eval(expr) {
+                        Ok(output) => println!("{output}"),
+                        Err(error) => {
+                            println!("{error:#}");
+                            break;
+                        }
                     }
                 }
             }
             Err(error) => println!("{error}"),
         }
     }
+    Ok(())
+}
+
+fn main() {
+    let options = match options(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{error}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    // Deep recursion in Lisp is deep recursion in the evaluator, so it runs
+    // on a thread with more stack than the main one
+    let thread = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| run(options));
+    let result = match thread {
+        Ok(thread) => thread
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic)),
+        Err(error) => Err(error.into()),
+    };
+    if let Err(error) = result {
+        eprintln!("{error:#}");
+        std::process::exit(1);
+    }
 }
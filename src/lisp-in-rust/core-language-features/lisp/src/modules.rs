@@ -0,0 +1,156 @@
+use crate::{
+    environment::Environment,
+    eval::eval,
+    expr::{Atom, Expr},
+    parser::parse,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+// Modules
+struct Module {
+    environment: Environment,
+    exports: Vec<String>,
+}
+
+#[derive(Default)]
+pub(crate) struct Modules {
+    loaded: HashMap<String, Module>,
+    // Modules that are being defined with the names they provide so far,
+    // innermost last
+    pending: Vec<(String, Vec<String>)>,
+    // Files that are being loaded, relative paths start at the last one
+    files: Vec<PathBuf>,
+}
+
+impl Modules {
+    // Functions in a module capture its namespace, so the namespaces are
+    // cleared for them to be freed
+    pub(crate) fn unload(self) {
+        for module in self.loaded.into_values() {
+            module.environment.clear();
+        }
+    }
+}
+
+pub(crate) fn resolve(path: &str, environment: &Environment) -> Result<PathBuf> {
+    let modules = environment.modules();
+    let modules = modules.borrow();
+    let directory = modules.files.last().and_then(|file| file.parent());
+    let path = match directory {
+        Some(directory) => directory.join(path),
+        None => PathBuf::from(path),
+    };
+    fs::canonicalize(&path).with_context(|| format!("Failed to find {}", path.display()))
+}
+
+pub(crate) fn load_file(path: &Path, environment: &Environment) -> Result<()> {
+    let modules = environment.modules();
+    if let Some(index) = modules.borrow().files.iter().position(|file| file == path) {
+        let cycle = modules.borrow().files[index..]
+            .iter()
+            .chain([&path.to_path_buf()])
+            .map(|file| file.display().to_string())
+            .collect::<Vec<_>>();
+        bail!("Cyclic load: {}", cycle.join(" -> "));
+    }
+    let body =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let exprs = parse(&body).map_err(|error| error.in_file(path))?;
+    modules.borrow_mut().files.push(path.to_path_buf());
+    let result = exprs
+        .iter()
+        .try_for_each(|expr| eval(expr, environment).map(drop));
+    modules.borrow_mut().files.pop();
+    result
+}
+
+// Evaluates a module body in its own namespace, so only the names it provides
+// are visible to the code that requires it
+pub(crate) fn define_module(
+    name: String,
+    environment: &Environment,
+    body: impl FnOnce(&Environment) -> Result<()>,
+) -> Result<()> {
+    let modules = environment.modules();
+    let namespace = environment.global().extend();
+    modules
+        .borrow_mut()
+        .pending
+        .push((name.clone(), Vec::new()));
+    let result = body(&namespace);
+    let (_, exports) = modules.borrow_mut().pending.pop().unwrap_or_default();
+    result?;
+    let module = Module {
+        environment: namespace,
+        exports,
+    };
+    modules.borrow_mut().loaded.insert(name, module);
+    Ok(())
+}
+
+pub(crate) fn provide(names: Vec<String>, environment: &Environment) -> Result<()> {
+    let modules = environment.modules();
+    let mut modules = modules.borrow_mut();
+    let Some((_, exports)) = modules.pending.last_mut() else {
+        bail!("`provide` can only be used in a module");
+    };
+    exports.extend(names);
+    Ok(())
+}
+
+// Files are loaded once and cached under their canonical path, while symbols
+// refer to modules defined with `module`
+pub(crate) fn require(spec: &Expr, environment: &Environment) -> Result<()> {
+    let (name, path) = match spec {
+        Expr::Constant(Atom::String(path)) => {
+            let path = resolve(path, environment)?;
+            (path.display().to_string(), Some(path))
+        }
+        Expr::Constant(Atom::Symbol(name)) => (name.clone(), None),
+        spec => bail!("`require` expected path or module name, got {spec}"),
+    };
+    let modules = environment.modules();
+    if let Some(index) = modules
+        .borrow()
+        .pending
+        .iter()
+        .position(|(pending, _)| *pending == name)
+    {
+        let cycle = modules.borrow().pending[index..]
+            .iter()
+            .map(|(pending, _)| pending.clone())
+            .chain([name.clone()])
+            .collect::<Vec<_>>();
+        bail!("Cyclic require: {}", cycle.join(" -> "));
+    }
+    if !modules.borrow().loaded.contains_key(&name) {
+        match path {
+            Some(path) => define_module(name.clone(), environment, |namespace| {
+                load_file(&path, namespace)
+            })?,
+            None => bail!("Unknown module `{name}`"),
+        }
+    }
+    let modules = modules.borrow();
+    let module = &modules.loaded[&name];
+    for export in &module.exports {
+        let value = module
+            .environment
+            .get(export)
+            .ok_or_else(|| anyhow!("`{export}` is provided by {name} but not defined"))?;
+        environment.define(export.clone(), value);
+    }
+    Ok(())
+}
+
+pub(crate) fn load(body: &str, environment: &Environment) -> Result<()> {
+    for expr in parse(body)? {
+        eval(&expr, environment)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use anyhow::{bail, Result};
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{ToPrimitive, Zero};
+use std::{
+    cmp::Ordering,
+    fmt,
+    ops::{Add, Mul, Neg, Sub},
+};
+
+// Numbers
+#[derive(Debug, Clone)]
+pub enum Number {
+    Integer(BigInt),
+    Rational(BigRational),
+    Float(f64),
+}
+
+impl Number {
+    // Exact results that happen to be whole are kept as integers
+    pub(crate) fn normalize(self) -> Number {
+        match self {
+            Number::Rational(ratio) if ratio.is_integer() => Number::Integer(ratio.to_integer()),
+            number => number,
+        }
+    }
+
+    // Integers and rationals are exact, floats are approximations
+    pub(crate) fn is_exact(&self) -> bool {
+        !matches!(self, Number::Float(_))
+    }
+
+    pub fn to_float(&self) -> f64 {
+        let float = match self {
+            Number::Integer(integer) => integer.to_f64(),
+            Number::Rational(ratio) => ratio.to_f64(),
+            Number::Float(float) => Some(*float),
+        };
+        float.unwrap_or(f64::NAN)
+    }
+
+    // Converts both numbers to the same kind: integers become rationals next to
+    // a rational, and anything mixed with a float becomes a float
+    fn promote(self, other: Number) -> (Number, Number) {
+        match (self, other) {
+            (Number::Float(a), b) => (Number::Float(a), Number::Float(b.to_float())),
+            (a, Number::Float(b)) => (Number::Float(a.to_float()), Number::Float(b)),
+            (Number::Integer(a), Number::Rational(b)) => (
+                Number::Rational(BigRational::from_integer(a)),
+                Number::Rational(b),
+            ),
+            (Number::Rational(a), Number::Integer(b)) => (
+                Number::Rational(a),
+                Number::Rational(BigRational::from_integer(b)),
+            ),
+            pair => pair,
+        }
+    }
+
+    // Dividing integers is exact, so `(/ 1 3)` is the rational 1/3. Floats
+    // follow IEEE 754 and divide by zero into infinity instead of failing
+    pub fn checked_div(self, other: Number) -> Result<Number> {
+        let quotient = match self.promote(other) {
+            (_, Number::Integer(b)) if b.is_zero() => bail!("Division by zero"),
+            (_, Number::Rational(b)) if b.is_zero() => bail!("Division by zero"),
+            (Number::Integer(a), Number::Integer(b)) => {
+                Number::Rational(BigRational::new(a, b)).normalize()
+            }
+            (Number::Rational(a), Number::Rational(b)) => Number::Rational(a / b).normalize(),
+            (a, b) => Number::Float(a.to_float() / b.to_float()),
+        };
+        Ok(quotient)
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Number::Integer(integer) => write!(f, "{integer}"),
+            Number::Rational(ratio) => write!(f, "{ratio}"),
+            Number::Float(float) if float.is_nan() => write!(f, "+nan.0"),
+            Number::Float(float) if float.is_infinite() && *float > 0.0 => write!(f, "+inf.0"),
+            Number::Float(float) if float.is_infinite() => write!(f, "-inf.0"),
+            Number::Float(float) => write!(f, "{float:?}"),
+        }
+    }
+}
+
+// Integers are arbitrary precision, so adding, subtracting and multiplying
+// them can't overflow and need no checked or wrapping variants. Dividing by
+// zero is the only arithmetic error, which `checked_div` reports
+impl Add for Number {
+    type Output = Number;
+
+    fn add(self, other: Number) -> Number {
+        match self.promote(other) {
+            (Number::Integer(a), Number::Integer(b)) => Number::Integer(a + b),
+            (Number::Rational(a), Number::Rational(b)) => Number::Rational(a + b).normalize(),
+            (a, b) => Number::Float(a.to_float() + b.to_float()),
+        }
+    }
+}
+
+impl Sub for Number {
+    type Output = Number;
+
+    fn sub(self, other: Number) -> Number {
+        match self.promote(other) {
+            (Number::Integer(a), Number::Integer(b)) => Number::Integer(a - b),
+            (Number::Rational(a), Number::Rational(b)) => Number::Rational(a - b).normalize(),
+            (a, b) => Number::Float(a.to_float() - b.to_float()),
+        }
+    }
+}
+
+impl Mul for Number {
+    type Output = Number;
+
+    fn mul(self, other: Number) -> Number {
+        match self.promote(other) {
+            (Number::Integer(a), Number::Integer(b)) => Number::Integer(a * b),
+            (Number::Rational(a), Number::Rational(b)) => Number::Rational(a * b).normalize(),
+            (a, b) => Number::Float(a.to_float() * b.to_float()),
+        }
+    }
+}
+
+impl Neg for Number {
+    type Output = Number;
+
+    fn neg(self) -> Number {
+        match self {
+            Number::Integer(integer) => Number::Integer(-integer),
+            Number::Rational(ratio) => Number::Rational(-ratio),
+            Number::Float(float) => Number::Float(-float),
+        }
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Number) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        match self.clone().promote(other.clone()) {
+            (Number::Integer(a), Number::Integer(b)) => a.partial_cmp(&b),
+            (Number::Rational(a), Number::Rational(b)) => a.partial_cmp(&b),
+            (a, b) => a.to_float().partial_cmp(&b.to_float()),
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{
+    environment::Environment,
+    eval::{code, eval, quote},
+    expr::{Atom, Expr},
+};
+use anyhow::{anyhow, bail, Result};
+
+// Parameters
+/// The parameter list of a lambda or macro, like
+/// `(a b &optional (c 1) &key (d 2) . rest)`.
+#[derive(Debug, Clone, Default)]
+pub struct Params {
+    pub(crate) required: Vec<String>,
+    // Optional and keyword parameters keep the code for their default value
+    pub(crate) optional: Vec<(String, Expr)>,
+    pub(crate) keys: Vec<(String, Expr)>,
+    pub(crate) rest: Option<String>,
+    // The function of a named let is bound to this name in every call
+    pub(crate) name: Option<String>,
+}
+
+#[derive(PartialEq)]
+enum Section {
+    Required,
+    Optional,
+    Key,
+    Rest,
+}
+
+impl Params {
+    // Reads a quoted parameter list, `args` alone takes every argument
+    pub(crate) fn from_data(data: &Expr) -> Result<Params> {
+        let mut params = Params::default();
+        let mut section = Section::Required;
+        let mut list = data;
+        loop {
+            let item = match list {
+                Expr::Pair(pair) => {
+                    list = &pair.1;
+                    &pair.0
+                }
+                Expr::Nil => break,
+                Expr::Constant(Atom::Symbol(name)) => {
+                    params.add_rest(name)?;
+                    break;
+                }
+                list => bail!("Expected parameter, got {list}"),
+            };
+            section = match item {
+                marker if marker.is_symbol("&optional") => Section::Optional,
+                marker if marker.is_symbol("&key") => Section::Key,
+                marker if marker.is_symbol("&rest") => Section::Rest,
+                _ => {
+                    params.add(&section, item)?;
+                    continue;
+                }
+            };
+        }
+        if section == Section::Rest && params.rest.is_none() {
+            bail!("`&rest` expects a name");
+        }
+        Ok(params)
+    }
+
+    fn add(&mut self, section: &Section, item: &Expr) -> Result<()> {
+        let defaults = matches!(section, Section::Optional | Section::Key);
+        let (name, default) = match item.list_items().ok().as_deref() {
+            Some([Expr::Constant(Atom::Symbol(name)), default]) if defaults => {
+                (name.clone(), code(default.clone()))
+            }
+            _ => match item {
+                Expr::Constant(Atom::Symbol(name)) => (name.clone(), Expr::Nil),
+                item => bail!("Expected parameter, got {item}"),
+            },
+        };
+        self.check_unique(&name)?;
+        match section {
+            Section::Required => self.required.push(name),
+            Section::Optional => self.optional.push((name, default)),
+            Section::Key => self.keys.push((name, default)),
+            Section::Rest => self.add_rest(&name)?,
+        }
+        Ok(())
+    }
+
+    fn add_rest(&mut self, name: &str) -> Result<()> {
+        if self.rest.is_some() {
+            bail!("Only one rest parameter is allowed");
+        }
+        self.check_unique(name)?;
+        self.rest = Some(name.to_string());
+        Ok(())
+    }
+
+    fn check_unique(&self, name: &str) -> Result<()> {
+        let mut names = self
+            .required
+            .iter()
+            .chain(self.optional.iter().map(|(name, _)| name))
+            .chain(self.keys.iter().map(|(name, _)| name))
+            .chain(&self.rest);
+        if names.any(|other| other == name) {
+            bail!("Duplicate parameter `{name}`");
+        }
+        Ok(())
+    }
+
+    // The inverse of `from_data`, used when quoting a lambda
+    pub(crate) fn to_data(&self) -> Expr {
+        let symbol = |name: &str| Expr::symbol(name);
+        let with_default = |(name, default): &(String, Expr)| match default {
+            Expr::Nil => symbol(name),
+            default => Expr::list(vec![symbol(name), quote(default.clone())]),
+        };
+        let mut items: Vec<Expr> = self.required.iter().map(|name| symbol(name)).collect();
+        if !self.optional.is_empty() {
+            items.push(symbol("&optional"));
+            items.extend(self.optional.iter().map(with_default));
+        }
+        if !self.keys.is_empty() {
+            items.push(symbol("&key"));
+            items.extend(self.keys.iter().map(with_default));
+        }
+        let rest = match &self.rest {
+            Some(rest) => symbol(rest),
+            None => Expr::Nil,
+        };
+        Expr::dotted(items, rest)
+    }
+
+    fn arity(&self) -> String {
+        let min = self.required.len();
+        let max = min + self.optional.len();
+        let plural = |count| if count == 1 { "" } else { "s" };
+        match (self.rest.is_some() || !self.keys.is_empty(), min == max) {
+            (true, _) => format!("at least {min} argument{}", plural(min)),
+            (false, true) => format!("{min} argument{}", plural(min)),
+            (false, false) => format!("{min} to {max} arguments"),
+        }
+    }
+
+    // Binds the evaluated arguments of a call in `scope`. Defaults are
+    // evaluated in order, so they can refer to the parameters before them
+    pub(crate) fn bind(&self, values: Vec<Expr>, scope: &Environment) -> Result<()> {
+        let count = values.len();
+        let arity_error = || anyhow!("Expected {} for {self}, got {count}", self.arity());
+        let mut values = values.into_iter().peekable();
+        for name in &self.required {
+            let value = values.next().ok_or_else(arity_error)?;
+            scope.define(name.clone(), value);
+        }
+        for (name, default) in &self.optional {
+            // Keyword arguments start where the positional ones end
+            let keyword = !self.keys.is_empty()
+                && matches!(values.peek(), Some(Expr::Constant(Atom::Keyword(_))));
+            let value = match values.next_if(|_| !keyword) {
+                Some(value) => value,
+                None => eval(default, scope)?,
+            };
+            scope.define(name.clone(), value);
+        }
+        let rest: Vec<Expr> = values.collect();
+        if !self.keys.is_empty() {
+            self.bind_keys(&rest, scope)?;
+        }
+        match &self.rest {
+            Some(name) => scope.define(name.clone(), Expr::list(rest)),
+            None if !rest.is_empty() && self.keys.is_empty() => return Err(arity_error()),
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn bind_keys(&self, args: &[Expr], scope: &Environment) -> Result<()> {
+        let mut given = Vec::new();
+        for pair in args.chunks(2) {
+            match pair {
+                [Expr::Constant(Atom::Keyword(key)), value] => {
+                    if !self.keys.iter().any(|(name, _)| name == key) {
+                        bail!("Unknown keyword argument :{key}");
+                    }
+                    given.push((key, value));
+                }
+                [Expr::Constant(Atom::Keyword(key))] => bail!("Missing value for :{key}"),
+                [arg, ..] => bail!("Expected keyword argument, got {arg}"),
+                [] => {}
+            }
+        }
+        for (name, default) in &self.keys {
+            // Like Common Lisp, the first occurrence of a keyword wins
+            let value = match given.iter().find(|(key, _)| *key == name) {
+                Some((_, value)) => (*value).clone(),
+                None => eval(default, scope)?,
+            };
+            scope.define(name.clone(), value);
+        }
+        Ok(())
+    }
+}
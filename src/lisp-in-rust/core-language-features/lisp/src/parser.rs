@@ -0,0 +1,329 @@
+use crate::{
+    eval::quote,
+    expr::{Atom, Expr},
+    number::Number,
+    params::Params,
+};
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while},
+    character::complete::{anychar, char, digit1, multispace0, multispace1, none_of, satisfy},
+    combinator::{cut, eof, map, map_opt, map_res, opt, peek, recognize, value, verify},
+    multi::{many0, many1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    IResult,
+};
+use num_rational::BigRational;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+// Parser
+// Atom
+// Tokens like symbols and numbers have to end at whitespace, a paren, a string
+// or the end of input, so `definex` isn't `define` followed by `x`
+fn delimiter(input: &str) -> IResult<&str, ()> {
+    let options = alt((multispace1, tag("("), tag(")"), tag("\""), eof));
+    value((), peek(options))(input)
+}
+
+fn form_keyword<'a>(name: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    terminated(tag(name), delimiter)
+}
+
+fn boolean(input: &str) -> IResult<&str, Atom> {
+    let true_ = map(tag("#t"), |_| true);
+    let false_ = map(tag("#f"), |_| false);
+    map(alt((true_, false_)), Atom::Boolean)(input)
+}
+
+pub(crate) fn number(input: &str) -> IResult<&str, Atom> {
+    let sign = || opt(alt((char('+'), char('-'))));
+    let exponent = || recognize(tuple((alt((char('e'), char('E'))), sign(), digit1)));
+    let fraction = recognize(tuple((char('.'), digit1, opt(exponent()))));
+    let float = recognize(tuple((sign(), digit1, alt((fraction, exponent())))));
+    let special = alt((
+        value(f64::INFINITY, tag("+inf.0")),
+        value(f64::NEG_INFINITY, tag("-inf.0")),
+        value(f64::NAN, tag("+nan.0")),
+    ));
+    let rational = recognize(tuple((sign(), digit1, char('/'), digit1)));
+    let integer = recognize(pair(sign(), digit1));
+    let float = map_opt(float, |text: &str| text.parse().ok().map(Number::Float));
+    let special = map(special, Number::Float);
+    let rational = map_opt(rational, |text: &str| {
+        let ratio = text.parse::<BigRational>().ok()?;
+        Some(Number::Rational(ratio).normalize())
+    });
+    let integer = map_opt(integer, |text: &str| text.parse().ok().map(Number::Integer));
+    map(alt((special, float, rational, integer)), Atom::Number)(input)
+}
+
+fn string(input: &str) -> IResult<&str, Atom> {
+    let escape = alt((
+        value('"', char('"')),
+        value('\\', char('\\')),
+        value('\n', char('n')),
+        value('\t', char('t')),
+    ));
+    let character = alt((none_of("\\\""), preceded(char('\\'), escape)));
+    let string = delimited(char('"'), many0(character), cut(char('"')));
+    map(string, |chars| Atom::String(chars.into_iter().collect()))(input)
+}
+
+fn character(input: &str) -> IResult<&str, Atom> {
+    let named = alt((
+        value(' ', tag("space")),
+        value('\n', tag("newline")),
+        value('\t', tag("tab")),
+    ));
+    let character = preceded(tag("#\\"), alt((named, anychar)));
+    map(character, Atom::Char)(input)
+}
+
+// Letters, digits and `!$%&*/<=>?^_~+-.`, not starting with a digit. A lone
+// `.` is the dot of a dotted pair
+fn identifier(input: &str) -> IResult<&str, &str> {
+    let extended = |char: char| "!$%&*/<=>?^_~+-.".contains(char);
+    let initial = satisfy(move |char| char.is_alphabetic() || extended(char));
+    let subsequent = take_while(move |char: char| char.is_alphanumeric() || extended(char));
+    let identifier = recognize(pair(initial, subsequent));
+    verify(identifier, |name: &str| name != ".")(input)
+}
+
+fn symbol(input: &str) -> IResult<&str, Atom> {
+    map(identifier, |name: &str| Atom::Symbol(name.to_string()))(input)
+}
+
+// Keywords like :name evaluate to themselves
+fn keyword(input: &str) -> IResult<&str, Atom> {
+    let keyword = preceded(char(':'), identifier);
+    map(keyword, |name: &str| Atom::Keyword(name.to_string()))(input)
+}
+
+fn atom(input: &str) -> IResult<&str, Atom> {
+    // Numbers go before symbols, which could otherwise start with a sign
+    let token = alt((character, boolean, keyword, number, symbol));
+    let options = alt((string, terminated(token, delimiter)));
+    delimited(multispace0, options, multispace0)(input)
+}
+
+// Expr
+fn constant(input: &str) -> IResult<&str, Expr> {
+    map(atom, Expr::Constant)(input)
+}
+
+fn nil(input: &str) -> IResult<&str, Expr> {
+    map(pair(char('('), preceded(multispace0, char(')'))), |_| {
+        Expr::Nil
+    })(input)
+}
+
+// 'x, `x, ,x and ,@x are short for (quote x), (quasiquote x), (unquote x) and
+// (unquote-splicing x)
+fn quoted(input: &str) -> IResult<&str, Expr> {
+    let prefix = alt((
+        value("quote", tag("'")),
+        value("quasiquote", tag("`")),
+        value("unquote-splicing", tag(",@")),
+        value("unquote", tag(",")),
+    ));
+    let quoted = pair(prefix, cut(expr));
+    map(quoted, |(name, expr)| {
+        Expr::Call(Box::new(Expr::symbol(name)), vec![expr])
+    })(input)
+}
+
+fn call(input: &str) -> IResult<&str, Expr> {
+    let dotted = preceded(char('.'), cut(expr));
+    let form = tuple((expr, many0(expr), opt(dotted)));
+    let call = map(form, |(head, mut tail, last)| match last {
+        // `(a b . c)` can't be called, so it's read as a literal pair
+        Some(last) => {
+            tail.insert(0, head);
+            Expr::dotted(tail, last)
+        }
+        None => Expr::Call(Box::new(head), tail),
+    });
+    // Once a call has started, a missing `)` is a real error and shouldn't
+    // backtrack, so the failure points at where the problem actually is
+    delimited(tag("("), call, cut(tag(")")))(input)
+}
+
+fn define(input: &str) -> IResult<&str, Expr> {
+    // Anything else is read as a call, which `eval` reports
+    let name = verify(atom, |name| matches!(name, Atom::Symbol(_)));
+    let form = preceded(form_keyword("define"), pair(name, expr));
+    let define = map(form, |(name, value)| Expr::Define(name, Box::new(value)));
+    delimited(tag("("), define, tag(")"))(input)
+}
+
+fn lambda(input: &str) -> IResult<&str, Expr> {
+    // Parameters are read like any list, so `(a . rest)` and a lone `args`
+    // work, and invalid ones are left for `eval` to report
+    let params = map_res(expr, |params| Params::from_data(&quote(params)));
+    let form = preceded(form_keyword("lambda"), pair(params, many1(expr)));
+    let lambda = map(form, |(params, body)| {
+        Expr::Lambda(Rc::new(params), body.into())
+    });
+    delimited(tag("("), lambda, tag(")"))(input)
+}
+
+fn expr(input: &str) -> IResult<&str, Expr> {
+    let expr = alt((quoted, define, lambda, nil, call, constant));
+    delimited(multispace0, expr, multispace0)(input)
+}
+
+// Final parser
+pub fn parse(input: &str) -> Result<Vec<Expr>, ParseError> {
+    let rest = match terminated(many0(expr), multispace0)(input) {
+        Ok(("", exprs)) => return Ok(exprs),
+        Ok((rest, _)) => rest,
+        Err(nom::Err::Error(error) | nom::Err::Failure(error)) => error.input,
+        Err(nom::Err::Incomplete(_)) => "",
+    };
+    Err(diagnose(input, rest))
+}
+
+// Diagnostics
+#[derive(Debug)]
+pub struct ParseError {
+    message: String,
+    line: usize,
+    column: usize,
+    source: String,
+    file: Option<PathBuf>,
+    // Input ended inside a list or string, so more lines could complete it
+    incomplete: bool,
+}
+
+impl ParseError {
+    fn new(input: &str, offset: usize, message: String) -> Self {
+        let (line, column) = position(input, offset);
+        let source = input.lines().nth(line - 1).unwrap_or_default().to_string();
+        ParseError {
+            message,
+            line,
+            column,
+            source,
+            file: None,
+            incomplete: false,
+        }
+    }
+
+    /// Whether the input ended inside a list or string, so appending more
+    /// input could make it parse.
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+
+    pub(crate) fn in_file(self, file: &Path) -> Self {
+        ParseError {
+            file: Some(file.to_path_buf()),
+            ..self
+        }
+    }
+
+    fn unclosed(input: &str, offset: usize, name: &str) -> Self {
+        let (line, column) = position(input, offset);
+        let message = format!("unclosed {name} opened at {line}:{column}");
+        ParseError {
+            incomplete: true,
+            ..ParseError::new(input, offset, message)
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let gutter = " ".repeat(self.line.to_string().len());
+        let caret = self
+            .source
+            .chars()
+            .take(self.column - 1)
+            .map(|char| if char == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        let file = match &self.file {
+            Some(file) => format!("{}:", file.display()),
+            None => String::new(),
+        };
+        writeln!(f, "error: {}", self.message)?;
+        writeln!(f, "{gutter}--> {file}{}:{}", self.line, self.column)?;
+        writeln!(f, "{gutter} |")?;
+        writeln!(f, "{} | {}", self.line, self.source)?;
+        write!(f, "{gutter} | {caret}^")
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// 1-based line and column of a byte offset
+fn position(input: &str, offset: usize) -> (usize, usize) {
+    let before = &input[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
+
+// The offset of the denominator if `rest` starts with a literal like `1/0`,
+// which `number` rejects without saying why
+fn zero_denominator(rest: &str) -> Option<usize> {
+    let numerator = recognize(tuple((opt(alt((char('+'), char('-')))), digit1, char('/'))));
+    let literal: IResult<&str, (&str, &str)> = pair(numerator, terminated(digit1, delimiter))(rest);
+    let (_, (numerator, denominator)) = literal.ok()?;
+    denominator
+        .chars()
+        .all(|char| char == '0')
+        .then_some(numerator.len())
+}
+
+// nom only tells us where parsing stopped, so mismatched parentheses are found
+// by scanning the input, which points at the bracket that is actually wrong
+fn diagnose(input: &str, rest: &str) -> ParseError {
+    let offset = input.len() - rest.len();
+    let unexpected = match rest.chars().next() {
+        Some(char) => format!("unexpected `{char}`"),
+        None => "unexpected end of input".to_string(),
+    };
+    if let Some(denominator) = zero_denominator(rest) {
+        let message = "zero denominator in rational literal".to_string();
+        return ParseError::new(input, offset + denominator, message);
+    }
+    if !matches!(rest.chars().next(), None | Some(')')) {
+        return ParseError::new(input, offset, unexpected);
+    }
+    let mut opened = Vec::new();
+    let mut string = None;
+    let mut chars = input.char_indices();
+    while let Some((index, char)) = chars.next() {
+        match (char, string) {
+            // Escapes in strings and character literals like #\( hide the next char
+            ('\\', _) => {
+                chars.next();
+            }
+            ('"', None) => string = Some(index),
+            ('"', Some(_)) => string = None,
+            (_, Some(_)) => {}
+            ('(', None) => opened.push(index),
+            (')', None) if opened.pop().is_none() => {
+                return ParseError::new(input, index, "unexpected `)`".to_string());
+            }
+            _ => {}
+        }
+    }
+    if let Some(index) = string {
+        return ParseError::unclosed(input, index, "string");
+    }
+    match opened.pop() {
+        Some(index) => ParseError::unclosed(input, index, "parenthesis"),
+        None => ParseError::new(input, offset, unexpected),
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{
+    expr::{Atom, Expr},
+    params::Params,
+};
+use std::fmt;
+
+// Printer
+impl fmt::Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Atom::Boolean(true) => write!(f, "#t"),
+            Atom::Boolean(false) => write!(f, "#f"),
+            Atom::Number(number) => write!(f, "{number}"),
+            Atom::String(string) => {
+                write!(f, "\"")?;
+                for char in string.chars() {
+                    match char {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\t' => write!(f, "\\t")?,
+                        char => write!(f, "{char}")?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            Atom::Char(' ') => write!(f, "#\\space"),
+            Atom::Char('\n') => write!(f, "#\\newline"),
+            Atom::Char('\t') => write!(f, "#\\tab"),
+            Atom::Char(char) => write!(f, "#\\{char}"),
+            Atom::Symbol(name) => write!(f, "{name}"),
+            Atom::Keyword(name) => write!(f, ":{name}"),
+        }
+    }
+}
+
+// Values print as the source that reads back to them, except functions and
+// macros which have no written form
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Constant(atom) => write!(f, "{atom}"),
+            Expr::Define(name, value) => write!(f, "(define {name} {value})"),
+            Expr::Call(head, tail) if tail.is_empty() => write!(f, "({head})"),
+            Expr::Call(head, tail) => write!(f, "({head} {})", format_exprs(tail)),
+            Expr::Lambda(params, body) => write!(f, "(lambda {params} {})", format_exprs(body)),
+            Expr::Closure(params, _, _) => write!(f, "#<lambda {params}>"),
+            Expr::Macro(params, _, _) => write!(f, "#<macro {params}>"),
+            Expr::Builtin(builtin) => write!(f, "#<builtin {}>", builtin.name()),
+            Expr::Pair(pair) => {
+                write!(f, "({}", pair.0)?;
+                let mut rest = &pair.1;
+                while let Expr::Pair(pair) = rest {
+                    write!(f, " {}", pair.0)?;
+                    rest = &pair.1;
+                }
+                match rest {
+                    Expr::Nil => write!(f, ")"),
+                    last => write!(f, " . {last})"),
+                }
+            }
+            Expr::Nil => write!(f, "()"),
+        }
+    }
+}
+
+impl fmt::Display for Params {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_data())
+    }
+}
+
+pub(crate) fn format_exprs(exprs: &[Expr]) -> String {
+    let exprs = exprs.iter().map(ToString::to_string).collect::<Vec<_>>();
+    exprs.join(" ")
+}
@@ -1,3 +1,32 @@
-(define car (quote car))
-(define cdr (quote cdr))
-(define list (quote list))
+(define nil (quote ()))
+
+(define fold-left
+  (lambda (f initial list)
+    (if (null? list)
+        initial
+        (fold-left f (f initial (car list)) (cdr list)))))
+
+(define fold-right
+  (lambda (f initial list)
+    (fold-left (lambda (acc item) (f item acc)) initial (reverse list))))
+
+(define reduce
+  (lambda (f initial list)
+    (if (null? list)
+        initial
+        (fold-left f (car list) (cdr list)))))
+
+(define map
+  (lambda (f list)
+    (reverse (fold-left (lambda (acc item) (cons (f item) acc)) nil list))))
+
+(define filter
+  (lambda (keep? list)
+    (reverse
+     (fold-left (lambda (acc item) (if (keep? item) (cons item acc) acc)) nil list))))
+
+(define for-each
+  (lambda (f list)
+    (unless (null? list)
+      (f (car list))
+      (for-each f (cdr list)))))
@@ -0,0 +1,546 @@
+use lisp::{Builtin, Expr, Interpreter};
+use std::{fs, path::PathBuf, rc::Rc};
+
+// Evaluates `source` in a fresh interpreter and prints the last value
+fn eval(source: &str) -> String {
+    let interpreter = Interpreter::new();
+    match interpreter.eval_str(source) {
+        Ok(output) => output.to_string(),
+        Err(error) => panic!("{source} failed: {error:#}"),
+    }
+}
+
+// The error `source` fails with
+fn error(source: &str) -> String {
+    match Interpreter::new().eval_str(source) {
+        Ok(output) => panic!("{source} returned {output}"),
+        Err(error) => format!("{error:#}"),
+    }
+}
+
+// Writes `files` as (path, source) pairs into a fresh directory for `test`
+fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("lisp-{}-{test}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    for (path, source) in files {
+        let path = directory.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source).unwrap();
+    }
+    directory
+}
+
+// Tail calls
+#[test]
+fn tail_recursive_countdown_completes() {
+    let source = "
+        (define countdown (lambda (n) (if (= n 0) (quote done) (countdown (- n 1)))))
+        (countdown 100000)";
+    assert_eq!(eval(source), "done");
+}
+
+// Numbers
+#[test]
+fn zero_denominator_is_reported() {
+    let error = lisp::parse("(+ 1 1/0)").unwrap_err().to_string();
+    assert!(error.starts_with("error: zero denominator in rational literal"));
+    assert!(error.contains("--> 1:8"));
+}
+
+#[test]
+fn integer_division_is_exact() {
+    assert_eq!(eval("(/ 1 3)"), "1/3");
+    assert_eq!(eval("(/ 4 2)"), "2");
+    assert_eq!(eval("(+ 1/3 2/3)"), "1");
+}
+
+#[test]
+fn floats_are_contagious() {
+    assert_eq!(eval("(+ 1 0.5)"), "1.5");
+    assert_eq!(eval("(+ 1/2 0.5)"), "1.0");
+    assert_eq!(eval("(* 2 1.5)"), "3.0");
+}
+
+#[test]
+fn rationals_are_normalised() {
+    assert_eq!(eval("2/4"), "1/2");
+    assert_eq!(eval("4/2"), "2");
+    assert_eq!(eval("-6/4"), "-3/2");
+    assert_eq!(eval("(= 4/2 2)"), "#t");
+}
+
+#[test]
+fn integers_grow_past_64_bits() {
+    assert_eq!(
+        eval("(* 99999999999 99999999999)"),
+        "9999999999800000000001"
+    );
+    assert_eq!(eval("(+ 9223372036854775807 1)"), "9223372036854775808");
+}
+
+// Printer
+#[test]
+fn empty_list_prints_as_parens() {
+    assert_eq!(eval("(quote (a ()))"), "(a ())");
+    assert_eq!(eval("(quote (lambda () 1))"), "(lambda () 1)");
+    assert_eq!(eval("(null? (car (cdr (quote (a ())))))"), "#t");
+}
+
+// Parameters
+#[test]
+fn wrong_argument_counts_are_reported() {
+    let message = "Expected 2 arguments for (a b), got 1";
+    assert_eq!(error("((lambda (a b) a) 1)"), message);
+    let message = "Expected 2 arguments for (a b), got 3";
+    assert_eq!(error("((lambda (a b) a) 1 2 3)"), message);
+    let message = "Expected 1 to 2 arguments for (a &optional b), got 0";
+    assert_eq!(error("((lambda (a &optional b) a))"), message);
+    let message = "Expected at least 1 argument for (a . rest), got 0";
+    assert_eq!(error("((lambda (a . rest) a))"), message);
+}
+
+#[test]
+fn rest_parameters_collect_the_remaining_arguments() {
+    assert_eq!(eval("((lambda (a . rest) rest) 1 2 3)"), "(2 3)");
+    assert_eq!(eval("((lambda (a . rest) rest) 1)"), "()");
+    assert_eq!(eval("((lambda args args) 1 2)"), "(1 2)");
+    assert_eq!(eval("((lambda args args))"), "()");
+}
+
+#[test]
+fn defaults_see_earlier_parameters() {
+    let function = "(lambda (a &optional (b (* a 2)) (c (+ a b))) (list a b c))";
+    assert_eq!(eval(&format!("({function} 1)")), "(1 2 3)");
+    assert_eq!(eval(&format!("({function} 1 5)")), "(1 5 6)");
+    let function = "(lambda (&key (x 1) (y (+ x 1))) (list x y))";
+    assert_eq!(eval(&format!("({function})")), "(1 2)");
+    assert_eq!(eval(&format!("({function} :x 5)")), "(5 6)");
+    assert_eq!(eval(&format!("({function} :y 0 :x 5)")), "(5 0)");
+}
+
+#[test]
+fn bad_keyword_arguments_are_reported() {
+    let function = "(lambda (&key x) x)";
+    assert_eq!(
+        error(&format!("({function} :y 1)")),
+        "Unknown keyword argument :y"
+    );
+    assert_eq!(error(&format!("({function} :x)")), "Missing value for :x");
+    assert_eq!(
+        error(&format!("({function} 5)")),
+        "Expected keyword argument, got 5"
+    );
+}
+
+#[test]
+fn duplicate_parameters_are_rejected() {
+    for params in ["(a a)", "(a &optional a)", "(a &key (a 1))", "(a . a)"] {
+        let source = format!("(lambda {params} a)");
+        assert_eq!(error(&source), "Duplicate parameter `a`", "{params}");
+    }
+}
+
+// Named let
+#[test]
+fn named_let_scope_is_freed() {
+    // Every value made by `token` shares `marker`, so its count shows how
+    // many are still alive
+    let marker = Rc::new(());
+    let interpreter = Interpreter::new();
+    let shared = marker.clone();
+    interpreter.define_builtin("token", move |_| {
+        let marker = shared.clone();
+        let token = Builtin::new("token", move |_| {
+            let _ = &marker;
+            Ok(Expr::Nil)
+        });
+        Ok(Expr::Builtin(token))
+    });
+    let alive = Rc::strong_count(&marker);
+    // The loop's scope would keep the scope of `f` and its argument alive
+    let source = "
+        (define f (lambda (token) (let loop ((i 0)) (if (= i 3) i (loop (+ i 1))))))
+        (f (token))";
+    let output = interpreter.eval_str(source).unwrap();
+    assert_eq!(output.to_string(), "3");
+    assert_eq!(Rc::strong_count(&marker), alive);
+}
+
+#[test]
+fn dropped_interpreter_is_freed() {
+    let marker = Rc::new(());
+    let interpreter = Interpreter::new();
+    let shared = marker.clone();
+    interpreter.define_builtin("token", move |_| {
+        let marker = shared.clone();
+        let token = Builtin::new("token", move |_| {
+            let _ = &marker;
+            Ok(Expr::Nil)
+        });
+        Ok(Expr::Builtin(token))
+    });
+    // Both the global environment and the module's namespace hold functions
+    // that captured them
+    let source = "
+        (define kept (token))
+        (define get-kept (lambda () kept))
+        (module m (define hidden (token)) (define get-hidden (lambda () hidden)) (provide get-hidden))
+        (require m)";
+    interpreter.eval_str(source).unwrap();
+    let clone = interpreter.clone();
+    drop(interpreter);
+    assert!(Rc::strong_count(&marker) > 1);
+    drop(clone);
+    assert_eq!(Rc::strong_count(&marker), 1);
+}
+
+// Define
+#[test]
+fn malformed_define_says_what_is_wrong() {
+    assert_eq!(
+        error("(define x 1 2)"),
+        "`define` expects 2 arguments, got 3"
+    );
+    assert_eq!(error("(define)"), "`define` expects 2 arguments, got 0");
+    assert_eq!(error("(define 5 3)"), "`define` expected name, got 5");
+    assert_eq!(
+        error("(define (f x) x)"),
+        "`define` expected name, got (f x). Functions are defined with (define f (lambda (x) ...))"
+    );
+}
+
+// Let
+#[test]
+fn let_bindings_see_the_outer_scope() {
+    assert_eq!(eval("(define x 1) (let ((x 2) (y x)) y)"), "1");
+    assert_eq!(eval("(let () 1)"), "1");
+}
+
+#[test]
+fn let_star_bindings_see_the_ones_before() {
+    assert_eq!(eval("(let* ((x 1) (y (+ x 1))) y)"), "2");
+    assert_eq!(eval("(let* ((x 1) (f (lambda () x)) (x 2)) (f))"), "1");
+}
+
+#[test]
+fn letrec_functions_call_each_other() {
+    let source = "
+        (letrec ((even? (lambda (n) (if (= n 0) #t (odd? (- n 1)))))
+                 (odd? (lambda (n) (if (= n 0) #f (even? (- n 1))))))
+          (list (even? 10) (odd? 10)))";
+    assert_eq!(eval(source), "(#t #f)");
+}
+
+#[test]
+fn definitions_in_let_bodies_stay_local() {
+    for form in ["(let ((y 1))", "(let ()", "(let* ()", "(letrec ()"] {
+        let source = format!("(define x 1) {form} (define x 2) x) x");
+        assert_eq!(eval(&source), "1", "{form}");
+    }
+}
+
+// Macros
+#[test]
+fn quasiquote_fills_in_unquoted_values() {
+    assert_eq!(eval("(define xs (list 2 3)) `(1 ,@xs 4)"), "(1 2 3 4)");
+    assert_eq!(eval("`(1 ,@nil 2)"), "(1 2)");
+    assert_eq!(eval("(define x 5) `(a ,x (b ,(+ x 1)))"), "(a 5 (b 6))");
+}
+
+#[test]
+fn macros_get_their_arguments_unevaluated() {
+    let source = "
+        (defmacro swap! (a b) `(let ((tmp ,a)) (set! ,a ,b) (set! ,b tmp)))
+        (define x 1)
+        (define y 2)
+        (swap! x y)
+        (list x y)";
+    assert_eq!(eval(source), "(2 1)");
+    let source = "(defmacro add (a &optional (b 10)) `(+ ,a ,b)) (list (add 1) (add 1 2))";
+    assert_eq!(eval(source), "(11 3)");
+}
+
+#[test]
+fn macroexpand_1_expands_once() {
+    let macros = "
+        (defmacro my-if (c a b) `(cond (,c ,a) (else ,b)))
+        (defmacro my-unless (c . body) `(my-if ,c nil (begin ,@body)))";
+    assert_eq!(
+        eval(&format!("{macros} (macroexpand-1 '(my-unless #f 1 2))")),
+        "(my-if #f nil (begin 1 2))"
+    );
+    assert_eq!(
+        eval(&format!("{macros} (macroexpand '(my-unless #f 1 2))")),
+        "(cond (#f nil) (else (begin 1 2)))"
+    );
+    assert_eq!(eval(&format!("{macros} (macroexpand '(+ 1 2))")), "(+ 1 2)");
+}
+
+// Modules
+#[test]
+fn required_files_are_loaded_once() {
+    let directory = write_files(
+        "load_once",
+        &[
+            (
+                "main.lisp",
+                "(require \"counter.lisp\") (require \"counter.lisp\")",
+            ),
+            ("counter.lisp", "(set! loads (+ loads 1))"),
+        ],
+    );
+    let interpreter = Interpreter::new();
+    interpreter.eval_str("(define loads 0)").unwrap();
+    interpreter.load_file(directory.join("main.lisp")).unwrap();
+    interpreter.load_file(directory.join("main.lisp")).unwrap();
+    assert_eq!(interpreter.get_global("loads").unwrap().to_string(), "1");
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn cycles_are_reported() {
+    let directory = write_files(
+        "cycles",
+        &[
+            ("load-a.lisp", "(load \"load-b.lisp\")"),
+            ("load-b.lisp", "(load \"load-a.lisp\")"),
+            ("require-a.lisp", "(require \"require-b.lisp\")"),
+            ("require-b.lisp", "(require \"require-a.lisp\")"),
+        ],
+    );
+    let interpreter = Interpreter::new();
+    let error = interpreter.load_file(directory.join("load-a.lisp"));
+    assert!(format!("{:#}", error.unwrap_err()).contains("Cyclic load: "));
+    let path = directory.join("require-a.lisp").display().to_string();
+    let error = interpreter.eval_str(&format!("(require {path:?})"));
+    assert!(format!("{:#}", error.unwrap_err()).contains("Cyclic require: "));
+    // The failed loads don't leave anything behind that breaks the next one
+    let error = interpreter.load_file(directory.join("load-a.lisp"));
+    assert!(format!("{:#}", error.unwrap_err()).contains("Cyclic load: "));
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn paths_are_relative_to_the_loading_file() {
+    let directory = write_files(
+        "relative",
+        &[
+            ("main.lisp", "(load \"lib/helper.lisp\")"),
+            ("lib/helper.lisp", "(load \"value.lisp\")"),
+            ("lib/value.lisp", "(define value 42)"),
+        ],
+    );
+    let interpreter = Interpreter::new();
+    interpreter.load_file(directory.join("main.lisp")).unwrap();
+    assert_eq!(interpreter.get_global("value").unwrap().to_string(), "42");
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn only_provided_names_are_visible() {
+    let directory = write_files(
+        "provide",
+        &[(
+            "module.lisp",
+            "(define shown 1) (define hidden 2) (define get-hidden (lambda () hidden)) (provide shown get-hidden)",
+        )],
+    );
+    let interpreter = Interpreter::new();
+    let path = directory.join("module.lisp").display().to_string();
+    interpreter
+        .eval_str(&format!("(require {path:?})"))
+        .unwrap();
+    let output = interpreter.eval_str("(list shown (get-hidden))").unwrap();
+    assert_eq!(output.to_string(), "(1 2)");
+    assert!(interpreter.get_global("hidden").is_none());
+    fs::remove_dir_all(directory).unwrap();
+    let source = "(module m (define a 1) (define b 2) (provide a)) (require m) a";
+    assert_eq!(eval(source), "1");
+    assert_eq!(error(&format!("{source} b")), "`b` is not defined");
+}
+
+// Parser
+#[test]
+fn character_literals_end_at_a_delimiter() {
+    assert!(lisp::parse("#\\ab").is_err());
+    assert!(lisp::parse("#\\spacex").is_err());
+    assert_eq!(
+        eval("(list #\\a #\\( #\\) #\\space)"),
+        "(#\\a #\\( #\\) #\\space)"
+    );
+}
+
+// Arithmetic
+#[test]
+fn unary_minus_negates() {
+    assert_eq!(eval("(- 0.0)"), "-0.0");
+    assert_eq!(eval("(- 5)"), "-5");
+    assert_eq!(eval("(- 1/2)"), "-1/2");
+    assert_eq!(eval("(- 5 2 1)"), "2");
+}
+
+#[test]
+fn exact_division_by_zero_fails() {
+    assert_eq!(error("(/ 1 0)"), "Division by zero");
+    assert_eq!(error("(/ 1 0/1)"), "Division by zero");
+    assert_eq!(error("(/ 1/2 0)"), "Division by zero");
+    assert_eq!(error("(/ 0)"), "Division by zero");
+    assert_eq!(eval("(/ 1 0.0)"), "+inf.0");
+    assert_eq!(eval("(/ -1 0.0)"), "-inf.0");
+}
+
+#[test]
+fn arithmetic_errors_keep_the_session() {
+    let interpreter = Interpreter::new();
+    interpreter.eval_str("(define x 1)").unwrap();
+    assert!(interpreter.eval_str("(+ x (/ 1 0))").is_err());
+    let output = interpreter.eval_str("(+ x 1)").unwrap();
+    assert_eq!(output.to_string(), "2");
+}
+
+// Higher-order functions
+#[test]
+fn apply_spreads_its_last_argument() {
+    assert_eq!(eval("(apply + 1 2 (list 3 4))"), "10");
+    assert_eq!(eval("(apply (lambda (x) (* x x)) (list 3))"), "9");
+    assert_eq!(eval("(apply apply (list + (list 1 2)))"), "3");
+}
+
+#[test]
+fn apply_is_a_value() {
+    assert_eq!(eval("(define ap apply) (ap - (list 10 1))"), "9");
+    assert_eq!(
+        eval("(map (lambda (f) (apply f (list 2 3))) (list + *))"),
+        "(5 6)"
+    );
+}
+
+#[test]
+fn apply_in_tail_position_is_a_tail_call() {
+    let source = "
+        (define countdown (lambda (n) (if (= n 0) (quote done) (apply countdown (list (- n 1))))))
+        (countdown 100000)";
+    assert_eq!(eval(source), "done");
+}
+
+// Lists
+#[test]
+fn map_applies_a_function_to_each_item() {
+    assert_eq!(eval("(map (lambda (x) (* x x)) (list 1 2 3))"), "(1 4 9)");
+    assert_eq!(eval("(map car nil)"), "()");
+}
+
+#[test]
+fn filter_keeps_matching_items() {
+    assert_eq!(eval("(filter (lambda (x) (> x 1)) (list 1 2 3))"), "(2 3)");
+    assert_eq!(eval("(filter pair? (list 1 2))"), "()");
+}
+
+#[test]
+fn reduce_starts_from_the_first_item() {
+    assert_eq!(eval("(reduce + 0 (list 1 2 3))"), "6");
+    assert_eq!(eval("(reduce - 0 (list 10 1 2))"), "7");
+    assert_eq!(eval("(reduce + 0 nil)"), "0");
+}
+
+#[test]
+fn folds_go_in_opposite_directions() {
+    assert_eq!(eval("(fold-left - 0 (list 1 2 3))"), "-6");
+    assert_eq!(eval("(fold-right - 0 (list 1 2 3))"), "2");
+    assert_eq!(eval("(fold-right cons nil (list 1 2 3))"), "(1 2 3)");
+    assert_eq!(eval("(fold-left cons nil (list 1 2))"), "((() . 1) . 2)");
+}
+
+#[test]
+fn for_each_calls_a_function_in_order() {
+    let source = "
+        (define seen nil)
+        (for-each (lambda (x) (set! seen (cons x seen))) (list 1 2 3))
+        seen";
+    assert_eq!(eval(source), "(3 2 1)");
+}
+
+#[test]
+fn assoc_finds_entries_by_key() {
+    let entries = "(list (cons 1 \"a\") (cons (list 2) \"b\"))";
+    assert_eq!(eval(&format!("(assoc 1 {entries})")), "(1 . \"a\")");
+    assert_eq!(
+        eval(&format!("(assoc (list 2) {entries})")),
+        "((2) . \"b\")"
+    );
+    assert_eq!(eval(&format!("(assoc 3 {entries})")), "#f");
+    assert_eq!(error("(assoc 1 (list 1))"), "`assoc` expected pair, got 1");
+}
+
+#[test]
+fn member_returns_the_rest_of_the_list() {
+    assert_eq!(eval("(member 2 (list 1 2 3))"), "(2 3)");
+    assert_eq!(eval("(member 4 (list 1 2 3))"), "#f");
+}
+
+#[test]
+fn append_joins_lists() {
+    assert_eq!(
+        eval("(append (list 1) (list 2 3) nil (list 4))"),
+        "(1 2 3 4)"
+    );
+    assert_eq!(eval("(append (list 1) 2)"), "(1 . 2)");
+    assert_eq!(eval("(append)"), "()");
+}
+
+#[test]
+fn reverse_and_length() {
+    assert_eq!(eval("(reverse (list 1 2 3))"), "(3 2 1)");
+    assert_eq!(eval("(reverse nil)"), "()");
+    assert_eq!(eval("(length (list 1 2 3))"), "3");
+    assert_eq!(eval("(length nil)"), "0");
+}
+
+#[test]
+fn nth_indexes_from_zero() {
+    assert_eq!(eval("(nth 0 (list 1 2 3))"), "1");
+    assert_eq!(eval("(nth 2 (list 1 2 3))"), "3");
+    let message = "`nth` index 3 is out of bounds for length 3";
+    assert_eq!(error("(nth 3 (list 1 2 3))"), message);
+}
+
+#[test]
+fn equal_compares_exactness() {
+    assert_eq!(eval("(equal? 1 1.0)"), "#f");
+    assert_eq!(eval("(equal? (list 1/2) (list 0.5))"), "#f");
+    assert_eq!(eval("(equal? 2 4/2)"), "#t");
+    assert_eq!(eval("(equal? 1.5 3/2)"), "#f");
+    assert_eq!(eval("(= 1 1.0)"), "#t");
+    assert_eq!(eval("(member 1.0 (list 1 2))"), "#f");
+}
+
+#[test]
+fn equal_compares_long_lists() {
+    let source = "
+        (define range (lambda (n acc) (if (= n 0) acc (range (- n 1) (cons n acc)))))
+        (define big (range 100000 nil))";
+    let interpreter = Interpreter::new();
+    interpreter.eval_str(source).unwrap();
+    let equal = |other: &str| {
+        let output = interpreter.eval_str(&format!("(equal? big {other})"));
+        output.unwrap().to_string()
+    };
+    assert_eq!(equal("(range 100000 nil)"), "#t");
+    assert_eq!(equal("(range 99999 nil)"), "#f");
+    assert_eq!(equal("(cons 0 (cdr big))"), "#f");
+}
+
+// Recursion
+#[test]
+fn deep_recursion_fails_instead_of_overflowing() {
+    let interpreter = Interpreter::new();
+    let source = "(define f (lambda (n) (if (= n 0) 0 (+ 1 (f (- n 1))))))";
+    interpreter.eval_str(source).unwrap();
+    let output = interpreter.eval_str("(f 50)").unwrap();
+    assert_eq!(output.to_string(), "50");
+    let error = interpreter.eval_str("(f 100000)").unwrap_err();
+    assert_eq!(error.to_string(), "Recursion too deep");
+    // The interpreter is still usable afterwards
+    let output = interpreter.eval_str("(f 50)").unwrap();
+    assert_eq!(output.to_string(), "50");
+}
@@ -3,7 +3,7 @@ use nom::{
     branch::alt,
     bytes::complete::tag,
     character::complete::{alpha1, digit1, multispace0, multispace1},
-    combinator::map,
+    combinator::{map, map_res},
     multi::{many0, many1},
     sequence::{delimited, pair, preceded, separated_pair, terminated},
     IResult,
@@ -37,10 +37,9 @@ fn operator(input: &str) -> IResult<&str, Atom> {
     map(alt((plus, minus, divide, multiply)), Atom::Operator)(input)
 }
 
+// Literals too big for an isize fail to parse instead of panicking
 fn number(input: &str) -> IResult<&str, Atom> {
-    map(digit1, |digits: &str| {
-        Atom::Number(digits.parse::<isize>().unwrap())
-    })(input)
+    map_res(digit1, |digits: &str| digits.parse().map(Atom::Number))(input)
 }
 
 fn symbol(input: &str) -> IResult<&str, Atom> {
@@ -129,21 +128,21 @@ fn eval(expr: Expr, environment: &mut HashMap<String, Expr>) -> Result<Expr> {
                 .collect::<Result<Vec<_>, _>>()?;
             match head {
                 Expr::Constant(Atom::Operator(operator)) => {
-                    let numbers = exprs_to_numbers(&tail)?;
-                    let total = numbers
-                        .into_iter()
-                        .reduce(|total, number| match operator {
-                            Operator::Plus => total + number,
-                            Operator::Minus => total - number,
-                            Operator::Divide => total / number,
-                            Operator::Multiply => total * number,
-                        })
-                        .ok_or_else(|| anyhow!("Tail is empty"))?;
+                    let mut numbers = exprs_to_numbers(&tail)?.into_iter();
+                    let first = numbers.next().ok_or_else(|| anyhow!("Tail is empty"))?;
+                    let overflow = || anyhow!("Integer overflow");
+                    let total = numbers.try_fold(first, |total, number| match operator {
+                        Operator::Plus => total.checked_add(number).ok_or_else(overflow),
+                        Operator::Minus => total.checked_sub(number).ok_or_else(overflow),
+                        Operator::Divide if number == 0 => Err(anyhow!("Division by zero")),
+                        Operator::Divide => total.checked_div(number).ok_or_else(overflow),
+                        Operator::Multiply => total.checked_mul(number).ok_or_else(overflow),
+                    })?;
                     Expr::Constant(Atom::Number(total))
                 }
                 Expr::Lambda(args, body) => {
                     let scope = environment.clone();
-                    for (arg, expr) in args.into_iter().zip(tail.into_iter()) {
+                    for (arg, expr) in args.into_iter().zip(tail) {
                         match arg {
                             Atom::Symbol(name) => {
                                 environment.insert(name, expr);
@@ -151,9 +150,11 @@ fn eval(expr: Expr, environment: &mut HashMap<String, Expr>) -> Result<Expr> {
                             _ => return Err(anyhow!("Invalid symbol: {arg:?}")),
                         }
                     }
-                    let output = eval(*body, environment)?;
+                    // Restore the scope before returning errors too, so the
+                    // arguments don't stay defined
+                    let output = eval(*body, environment);
                     *environment = scope;
-                    output
+                    output?
                 }
                 _ => return Err(anyhow!("Invalid function: {head:?}")),
             }
@@ -186,13 +187,17 @@ fn main() -> Result<()> {
             Ok(input) => match parse(&input) {
                 Ok((_, exprs)) => {
                     for expr in exprs {
-                        let output = eval(expr, &mut environment)?;
-                        println!("{output:?}");
+                        match eval(expr, &mut environment) {
+                            Ok(output) => println!("{output:?}"),
+                            Err(error) => println!("{error}"),
+                        }
                     }
                 }
                 Err(error) => println!("{error}"),
             },
-            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            // Ctrl-C cancels the line and Ctrl-D quits
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
             Err(error) => {
                 println!("Error: {error}");
                 break;